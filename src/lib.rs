@@ -0,0 +1,3 @@
+pub use transaction_engine::*;
+
+mod transaction_engine;
\ No newline at end of file
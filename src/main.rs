@@ -1,18 +1,128 @@
 use std::env::args;
 use std::error::Error;
 use std::fs::File;
+use std::io::{stdin, stdout, BufWriter, IsTerminal, Write};
+use std::path::Path;
 use string_error::new_err;
-use transaction_engine::process_csv_transactions_and_return_csv_client_states;
+use transaction_engine::{
+    client_summary_line, process_interactive, process_stream_with_config, process_streaming,
+    write_client_states, InputFormat, OutputFormat, ProcessingConfig, ProcessingMode,
+    ProcessingWarning, TransactionTypeFilter, UnknownTransactionPolicy,
+};
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let file_path = args().nth(1).ok_or(new_err(
+    let mut file_path = None;
+    let mut output_path = None;
+    let mut output_format = None;
+    let mut only_transaction_types = None;
+    let mut client_summary = false;
+    let mut mode = ProcessingMode::default();
+    let mut args = args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--output" => {
+                output_path = Some(args.next().ok_or(new_err("--output requires a path"))?)
+            }
+            "--format" => {
+                output_format = Some(
+                    args.next()
+                        .ok_or(new_err("--format requires one of csv, csv.gz, json"))?
+                        .parse::<OutputFormat>()?,
+                )
+            }
+            "--type" => {
+                only_transaction_types =
+                    Some(TransactionTypeFilter::parse_list(&args.next().ok_or(
+                        new_err("--type requires a comma-separated list of transaction types"),
+                    )?)?)
+            }
+            "--client-summary" => client_summary = true,
+            "--mode" => {
+                mode = args
+                    .next()
+                    .ok_or(new_err(
+                        "--mode requires one of batch, streaming, interactive",
+                    ))?
+                    .parse()?
+            }
+            _ if arg.starts_with("--") => return Err(new_err(&format!("unknown argument {arg}"))),
+            _ => file_path = Some(arg),
+        }
+    }
+
+    if mode != ProcessingMode::Batch {
+        let batch_only_flag = [
+            ("--format", output_format.is_some()),
+            ("--type", only_transaction_types.is_some()),
+            ("--client-summary", client_summary),
+        ]
+        .into_iter()
+        .find_map(|(flag, given)| given.then_some(flag));
+        if let Some(flag) = batch_only_flag {
+            return Err(new_err(&format!("{flag} only applies to --mode batch")));
+        }
+    }
+
+    if mode == ProcessingMode::Interactive {
+        if output_path.is_some() {
+            return Err(new_err(
+                "--mode interactive always writes to stdout, not --output",
+            ));
+        }
+        process_interactive(stdin().lock(), stdout().lock())?;
+        return Ok(());
+    }
+    let file_path = file_path.ok_or(new_err(
         "one commandline argument as path to csv file is required",
     ))?;
     let file = File::open(file_path)?;
-    let csv_output = process_csv_transactions_and_return_csv_client_states(file)?;
-    print!("{csv_output}");
+    let (output, output_format): (Box<dyn Write>, _) = match output_path {
+        None => (Box::new(stdout().lock()), output_format.unwrap_or_default()),
+        Some(output_path) => {
+            // Before creating the file, so that an unsupported extension doesn't leave an empty one behind.
+            let output_format = match output_format {
+                Some(output_format) => output_format,
+                None => OutputFormat::from_path(Path::new(&output_path))?,
+            };
+            (
+                Box::new(BufWriter::new(File::create(&output_path)?)),
+                output_format,
+            )
+        }
+    };
+    if mode == ProcessingMode::Streaming {
+        // Always CSV, --format was rejected above.
+        process_streaming(file, InputFormat::Auto, output)?;
+        return Ok(());
+    }
+    let config = ProcessingConfig {
+        unknown_transaction_policy: UnknownTransactionPolicy::Ignore,
+        only_transaction_types,
+        ..Default::default()
+    };
+    let report = process_stream_with_config(file, InputFormat::Auto, &config)?;
+    let skipped = report
+        .warnings
+        .iter()
+        .filter(|warning| matches!(warning, ProcessingWarning::FilteredByType { .. }))
+        .count();
+    if skipped != 0 {
+        eprintln!("{skipped} transactions skipped by --type");
+    }
+    let clients = report.client_states.into_sorted_vec();
+    let summary_lines: Vec<_> = if client_summary {
+        let color = stdout().is_terminal();
+        clients
+            .iter()
+            .map(|(client_id, client_state)| client_summary_line(*client_id, client_state, color))
+            .collect()
+    } else {
+        Vec::new()
+    };
+    write_client_states(clients, output, output_format)?;
+    for summary_line in summary_lines {
+        println!("{summary_line}");
+    }
 
     Ok(())
 }
-
-mod transaction_engine;
\ No newline at end of file
@@ -0,0 +1,808 @@
+use super::output_format::strip_schema_version;
+use super::transaction_processing_logic::{apply_transaction, log_ignored};
+use super::{
+    compact_deposit_log, write_client_states, AmountType, ClientId, ClientState, DepositLog,
+    OutputCsvRecord, OutputFormat, ProcessingError, ProcessingWarning, Transaction, TransactionId,
+    TransactionType,
+};
+use csv::{Reader, Writer, WriterBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{self, Read};
+
+/// Keeps the client states and the deposit log between calls, so that new transactions can be appended without
+/// reprocessing the earlier ones. Ignored transactions are logged to stderr, like in the batch functions.
+#[derive(Default, Debug)]
+pub struct AccountLedger {
+    clients: HashMap<ClientId, ClientState>,
+    deposit_log: DepositLog,
+    warnings: Vec<ProcessingWarning>,
+    /// Every appended transaction in arrival order, and whether it has been applied, for `backfill_transactions`. Only
+    /// kept by a ledger created with `with_history`, since it grows with every transaction.
+    history: Option<Vec<(Transaction, Applied)>>,
+}
+
+type Applied = bool;
+
+impl AccountLedger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A ledger that also keeps every appended transaction, so that it can be backfilled with `backfill_transactions`.
+    /// Its memory use grows with the number of transactions rather than of clients and deposits.
+    pub fn with_history() -> Self {
+        Self {
+            history: Some(Vec::new()),
+            ..Self::default()
+        }
+    }
+
+    /// Restores a ledger from the output of `to_csv` and `to_deposit_log_csv`; `clients_csv` may start with a schema
+    /// version line, see `WriterConfig::include_schema_version`. The `total` column is ignored, since it
+    /// is derived from the other two. Transactions pending on locked clients are not part of the snapshot, and neither
+    /// is the history, so a restored ledger can't be backfilled.
+    pub fn from_snapshot(
+        clients_csv: &str,
+        deposit_log_csv: &str,
+    ) -> Result<Self, ProcessingError> {
+        // Version 1 is the only one so far; older formats will be told apart here.
+        let (_version, clients_csv) = strip_schema_version(clients_csv)?;
+
+        let mut ledger = Self::new();
+        for record in Reader::from_reader(clients_csv.as_bytes()).into_deserialize() {
+            let record: OutputCsvRecord = record?;
+            ledger.clients.insert(
+                record.client,
+                ClientState {
+                    available: record.available,
+                    held: record.held,
+                    locked: record.locked,
+                    ..Default::default()
+                },
+            );
+        }
+        for record in Reader::from_reader(deposit_log_csv.as_bytes()).into_deserialize() {
+            let record: DepositLogCsvRecord = record?;
+            ledger.deposit_log.insert(
+                record.tx,
+                (record.client, record.amount, record.under_dispute),
+            );
+        }
+        Ok(ledger)
+    }
+
+    pub fn append(&mut self, tx: Transaction) {
+        self.append_and_return_applied(tx);
+    }
+
+    fn append_and_return_applied(&mut self, tx: Transaction) -> Applied {
+        let result = apply_transaction(&mut self.clients, &mut self.deposit_log, &tx);
+        if let Err(reason) = result {
+            log_ignored(&tx, &reason);
+        }
+        if let Some(history) = &mut self.history {
+            history.push((tx, result.is_ok()));
+        }
+        result.is_ok()
+    }
+
+    pub fn append_many(&mut self, txs: impl IntoIterator<Item = Transaction>) {
+        for tx in txs {
+            self.append(tx);
+        }
+    }
+
+    /// Appends the transactions all-or-nothing: if one of them would corrupt the ledger, the ledger is restored to its
+    /// state before the batch and a `FatalError` is returned. That is the case for a deposit reusing the id of one on
+    /// record, which would overwrite it, and for a balance lowered below zero other than by a forced correction. Ignored
+    /// transactions don't roll the batch back.
+    pub fn apply_batch(
+        &mut self,
+        transactions: &[Transaction],
+    ) -> Result<BatchResult, ProcessingError> {
+        let clients = self.clients.clone();
+        let deposit_log = self.deposit_log.clone();
+        let warnings_len = self.warnings.len();
+        let history_len = self.history.as_ref().map(Vec::len);
+
+        let mut batch_result = BatchResult::default();
+        for tx in transactions {
+            match self.append_checked(tx) {
+                Ok(true) => batch_result.applied += 1,
+                Ok(false) => batch_result.ignored += 1,
+                Err(e) => {
+                    self.clients = clients;
+                    self.deposit_log = deposit_log;
+                    self.warnings.truncate(warnings_len);
+                    if let (Some(history), Some(history_len)) = (&mut self.history, history_len) {
+                        history.truncate(history_len);
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        Ok(batch_result)
+    }
+
+    /// Appends `tx`, failing if that leaves the ledger corrupt.
+    fn append_checked(&mut self, tx: &Transaction) -> Result<Applied, ProcessingError> {
+        let is_locked = self
+            .clients
+            .get(&tx.client)
+            .is_some_and(|client_state| client_state.locked);
+        if let (TransactionType::Deposit(_), false) = (&tx.tx_type, is_locked) {
+            if self.deposit_log.contains_key(&tx.tx) {
+                return Err(ProcessingError::FatalError(format!(
+                    "deposit {} reuses the id of a deposit on record",
+                    tx.tx
+                )));
+            }
+        }
+        let (available_before, held_before) = self
+            .clients
+            .get(&tx.client)
+            .map_or((AmountType::ZERO, AmountType::ZERO), |client_state| {
+                (client_state.available, client_state.held)
+            });
+        let applied = self.append_and_return_applied(tx.clone());
+        let client_state = &self.clients[&tx.client];
+        let is_forced_correction = matches!(tx.tx_type, TransactionType::Correction(_, true));
+        // Only a balance this transaction lowered below zero counts, the client may be negative from before.
+        let went_negative =
+            |after: AmountType, before: AmountType| after < AmountType::ZERO && after < before;
+        if (went_negative(client_state.available, available_before) && !is_forced_correction)
+            || went_negative(client_state.held, held_before)
+        {
+            return Err(ProcessingError::FatalError(format!(
+                "transaction {} left {:#} with a negative balance",
+                tx.tx, tx.client
+            )));
+        }
+        Ok(applied)
+    }
+
+    /// Unlocks `client` and applies the transactions that arrived while it was locked, in their arrival order. Returns
+    /// how many were applied; each of them also raises a `ProcessedAfterUnlock` warning. A pending chargeback locks the
+    /// client again, and the transactions after it go back to pending.
+    pub fn drain_pending(&mut self, client: ClientId) -> usize {
+        let pending = match self.clients.get_mut(&client) {
+            Some(client_state) => {
+                client_state.locked = false;
+                std::mem::take(&mut client_state.pending_locked_transactions)
+            }
+            None => return 0,
+        };
+
+        let mut processed = 0;
+        for tx in pending {
+            match apply_transaction(&mut self.clients, &mut self.deposit_log, &tx) {
+                Ok(()) => {
+                    processed += 1;
+                    if let Some(entry) = self
+                        .history
+                        .iter_mut()
+                        .flatten()
+                        .rev()
+                        .find(|(transaction, applied)| !applied && *transaction == tx)
+                    {
+                        entry.1 = true;
+                    }
+                    self.warnings.push(ProcessingWarning::ProcessedAfterUnlock {
+                        client: tx.client,
+                        tx: tx.tx,
+                    });
+                }
+                Err(reason) => log_ignored(&tx, &reason),
+            }
+        }
+        processed
+    }
+
+    /// Returns the warnings raised since the last call.
+    pub fn take_warnings(&mut self) -> Vec<ProcessingWarning> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    pub fn client_state(&self, id: ClientId) -> Option<&ClientState> {
+        self.clients.get(&id)
+    }
+
+    /// The client states in the same CSV format as the batch output, sorted by client id.
+    pub fn to_csv(&self) -> Result<String, ProcessingError> {
+        self.client_states_to_string(OutputFormat::Csv)
+    }
+
+    /// The client states as a JSON array, sorted by client id.
+    pub fn to_json(&self) -> Result<String, ProcessingError> {
+        self.client_states_to_string(OutputFormat::Json)
+    }
+
+    /// The deposit log as CSV with the columns `tx`, `client`, `amount` and `under_dispute`, sorted by transaction id.
+    /// Together with `to_csv`, this is everything needed to restore the ledger and resume appending.
+    pub fn to_deposit_log_csv(&self) -> Result<String, ProcessingError> {
+        let mut deposits: Vec<_> = self.deposit_log.iter().collect();
+        deposits.sort_unstable_by_key(|&(&tx, _)| tx);
+
+        let mut writer = Writer::from_writer(Vec::new());
+        for (&tx, &(client, amount, under_dispute)) in deposits {
+            writer.serialize(DepositLogCsvRecord {
+                tx,
+                client,
+                amount,
+                under_dispute,
+            })?;
+        }
+        let csv_output = writer.into_inner().map_err(|e| e.into_error())?;
+        Ok(
+            String::from_utf8(csv_output)
+                .expect("CSV output of ids, amounts and booleans is UTF-8"),
+        )
+    }
+
+    /// Drops the deposits that are not under dispute from the deposit log, see `compact_deposit_log`, and returns a
+    /// snapshot of the compacted ledger to persist. Those deposits can't be disputed anymore afterwards, so only
+    /// checkpoint once late disputes are no longer expected for them.
+    pub fn checkpoint(&mut self) -> Result<AccountLedgerCheckpoint, ProcessingError> {
+        let clients_csv = self.to_csv()?;
+        compact_deposit_log(&mut self.deposit_log);
+        Ok(AccountLedgerCheckpoint {
+            clients_csv,
+            deposit_log_csv: self.to_deposit_log_csv()?,
+        })
+    }
+
+    /// A pull-based counterpart of `to_csv`, e.g. to pipe the state into a socket or another process: the CSV is produced
+    /// one row at a time, as it is read.
+    pub fn csv_reader(&self) -> ClientStatesCsvReader<'_> {
+        let mut client_ids: Vec<_> = self.clients.keys().copied().collect();
+        client_ids.sort_unstable();
+        ClientStatesCsvReader {
+            ledger: self,
+            client_ids: client_ids.into_iter(),
+            header_written: false,
+            row: Vec::new(),
+            row_position: 0,
+        }
+    }
+
+    fn client_states_to_string(&self, format: OutputFormat) -> Result<String, ProcessingError> {
+        let mut clients: Vec<_> = self
+            .clients
+            .iter()
+            .map(|(&client_id, client_state)| (client_id, client_state.clone()))
+            .collect();
+        clients.sort_unstable_by_key(|&(client_id, _)| client_id);
+
+        let mut output = Vec::new();
+        write_client_states(clients, &mut output, format)?;
+        Ok(String::from_utf8(output).expect("client states are written as UTF-8"))
+    }
+}
+
+/// Reads the client states of an `AccountLedger` as CSV, sorted by client id, see `AccountLedger::csv_reader`.
+pub struct ClientStatesCsvReader<'a> {
+    ledger: &'a AccountLedger,
+    client_ids: std::vec::IntoIter<ClientId>,
+    header_written: bool,
+    /// The bytes of the current row, of which `row_position` have been read.
+    row: Vec<u8>,
+    row_position: usize,
+}
+
+impl ClientStatesCsvReader<'_> {
+    /// Replaces the exhausted row with the next one; returns false at the end of the output.
+    fn next_row(&mut self) -> io::Result<bool> {
+        let Some(client_id) = self.client_ids.next() else {
+            return Ok(false);
+        };
+        let mut writer = WriterBuilder::new()
+            .has_headers(!self.header_written)
+            .from_writer(std::mem::take(&mut self.row));
+        writer
+            .serialize(OutputCsvRecord::from((
+                client_id,
+                self.ledger.clients[&client_id].clone(),
+            )))
+            .map_err(io::Error::other)?;
+        self.row = writer
+            .into_inner()
+            .map_err(|e| io::Error::other(e.into_error()))?;
+        self.header_written = true;
+        Ok(true)
+    }
+}
+
+impl Read for ClientStatesCsvReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.row_position == self.row.len() {
+            self.row.clear();
+            self.row_position = 0;
+            if !self.next_row()? {
+                return Ok(0);
+            }
+        }
+        let len = (&self.row[self.row_position..]).read(buf)?;
+        self.row_position += len;
+        Ok(len)
+    }
+}
+
+/// How the transactions of a batch fared, see `AccountLedger::apply_batch`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BatchResult {
+    pub applied: usize,
+    pub ignored: usize,
+}
+
+/// A snapshot of an `AccountLedger`, to be restored with `AccountLedger::from_snapshot`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountLedgerCheckpoint {
+    pub clients_csv: String,
+    pub deposit_log_csv: String,
+}
+
+/// Inserts transactions that were missed into the history of `ledger`, and rebuilds its state by replaying the
+/// combined history sorted by transaction id. Only a ledger created with `AccountLedger::with_history` has a history to
+/// replay. Fails, leaving `ledger` untouched, if a transaction the ledger had applied would be ignored in the replay,
+/// e.g. a withdrawal that a backfilled dispute leaves uncovered. The replay knows nothing of unlocks made with
+/// `drain_pending`, so the transactions drained that way fail the check too.
+pub fn backfill_transactions(
+    ledger: &mut AccountLedger,
+    historical_txs: impl IntoIterator<Item = Transaction>,
+) -> Result<(), ProcessingError> {
+    let Some(history) = &ledger.history else {
+        return Err(ProcessingError::HistoryUnavailable);
+    };
+
+    let mut history: Vec<_> = history
+        .iter()
+        .cloned()
+        .chain(historical_txs.into_iter().map(|tx| (tx, false)))
+        .collect();
+    // A stable sort, so that a dispute still comes after the deposit with the same id.
+    history.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let mut replayed = AccountLedger::with_history();
+    for (tx, was_applied) in history {
+        let result = apply_transaction(&mut replayed.clients, &mut replayed.deposit_log, &tx);
+        if was_applied && result.is_err() {
+            return Err(ProcessingError::BackfillConflict(tx.tx));
+        }
+        if let Some(history) = &mut replayed.history {
+            history.push((tx, result.is_ok()));
+        }
+    }
+
+    replayed.warnings = std::mem::take(&mut ledger.warnings);
+    *ledger = replayed;
+    Ok(())
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct DepositLogCsvRecord {
+    tx: TransactionId,
+    client: ClientId,
+
+    #[serde(with = "rust_decimal::serde::str")]
+    amount: AmountType,
+
+    under_dispute: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_helpers::assert_client_state_equals;
+    use super::super::TransactionType;
+    use super::*;
+
+    /// The client states with what a snapshot keeps of them; the per-type counts aren't part of it.
+    fn snapshot_states(ledger: &AccountLedger) -> HashMap<ClientId, ClientState> {
+        ledger
+            .clients
+            .iter()
+            .map(|(&client_id, client_state)| {
+                (
+                    client_id,
+                    ClientState {
+                        available: client_state.available,
+                        held: client_state.held,
+                        locked: client_state.locked,
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_apply_batch() {
+        let transaction = |tx: u32, tx_type| Transaction {
+            client: 1.into(),
+            tx: tx.into(),
+            tx_type,
+        };
+        let mut ledger = AccountLedger::with_history();
+        assert_eq!(
+            ledger
+                .apply_batch(&[
+                    transaction(1, TransactionType::Deposit(2.into())),
+                    transaction(2, TransactionType::Withdrawal(3.into())),
+                ])
+                .unwrap(),
+            BatchResult {
+                applied: 1,
+                ignored: 1
+            }
+        );
+
+        let before = ledger.to_csv().unwrap();
+        assert!(matches!(
+            ledger.apply_batch(&[
+                transaction(3, TransactionType::Deposit(5.into())),
+                transaction(1, TransactionType::Deposit(1.into())),
+            ]),
+            Err(ProcessingError::FatalError(_))
+        ));
+        assert_eq!(ledger.to_csv().unwrap(), before);
+        assert_eq!(ledger.history.as_ref().unwrap().len(), 2);
+        assert_eq!(ledger.deposit_log.len(), 1);
+
+        // A client left negative by a forced correction can still deposit.
+        ledger
+            .apply_batch(&[transaction(
+                4,
+                TransactionType::Correction(AmountType::from(-5), true),
+            )])
+            .unwrap();
+        assert_eq!(
+            ledger
+                .apply_batch(&[transaction(5, TransactionType::Deposit(1.into()))])
+                .unwrap(),
+            BatchResult {
+                applied: 1,
+                ignored: 0
+            }
+        );
+        assert_eq!(
+            ledger.client_state(1.into()).unwrap().available,
+            AmountType::from(-2)
+        );
+    }
+
+    #[test]
+    fn test_csv_reader() {
+        let mut ledger = AccountLedger::new();
+        assert_eq!(io::read_to_string(ledger.csv_reader()).unwrap(), "");
+
+        for (client, tx) in [(2, 1), (1, 2)] {
+            ledger.append(Transaction {
+                client: client.into(),
+                tx: tx.into(),
+                tx_type: TransactionType::Deposit(AmountType::from_str_exact("1.5").unwrap()),
+            });
+        }
+        let mut reader = ledger.csv_reader();
+        let mut buf = [0; 4];
+        assert_eq!(reader.read(&mut buf).unwrap(), 4);
+        assert_eq!(&buf, b"clie");
+        let mut rest = String::new();
+        reader.read_to_string(&mut rest).unwrap();
+        assert_eq!(format!("clie{rest}"), ledger.to_csv().unwrap());
+    }
+
+    #[test]
+    fn test_append() {
+        let transactions = [
+            Transaction {
+                client: 1.into(),
+                tx: 1.into(),
+                tx_type: TransactionType::Deposit(AmountType::from_str_exact("3").unwrap()),
+            },
+            Transaction {
+                client: 1.into(),
+                tx: 2.into(),
+                tx_type: TransactionType::Deposit(AmountType::from_str_exact("1").unwrap()),
+            },
+            Transaction {
+                client: 1.into(),
+                tx: 1.into(),
+                tx_type: TransactionType::Dispute,
+            },
+        ];
+
+        let mut ledger = AccountLedger::new();
+        ledger.append_many(transactions[..2].to_vec());
+        assert_eq!(
+            ledger.client_state(1.into()).unwrap().available,
+            AmountType::from_str_exact("4").unwrap()
+        );
+
+        // The dispute refers to a deposit appended by the earlier call.
+        ledger.append(transactions[2].clone());
+        assert_client_state_equals(
+            &ledger.clients,
+            &HashMap::from([(
+                1.into(),
+                ClientState {
+                    available: AmountType::from_str_exact("1").unwrap(),
+                    held: AmountType::from_str_exact("3").unwrap(),
+                    #[cfg(feature = "detailed-stats")]
+                    deposit_count: 2,
+                    #[cfg(feature = "detailed-stats")]
+                    dispute_count: 1,
+                    ..Default::default()
+                },
+            )]),
+        );
+        assert_eq!(ledger.client_state(2.into()), None);
+    }
+
+    #[test]
+    fn test_snapshot_output() {
+        let mut ledger = AccountLedger::new();
+        ledger.append_many([
+            Transaction {
+                client: 2.into(),
+                tx: 2.into(),
+                tx_type: TransactionType::Deposit(AmountType::from_str_exact("2.5").unwrap()),
+            },
+            Transaction {
+                client: 1.into(),
+                tx: 1.into(),
+                tx_type: TransactionType::Deposit(AmountType::from_str_exact("1").unwrap()),
+            },
+            Transaction {
+                client: 2.into(),
+                tx: 2.into(),
+                tx_type: TransactionType::Dispute,
+            },
+        ]);
+
+        assert_eq!(
+            ledger.to_csv().unwrap(),
+            "client,available,held,total,locked\n1,1,0,1,false\n2,0.0,2.5,2.5,false\n"
+        );
+        assert_eq!(
+            ledger.to_json().unwrap(),
+            r#"[{"client":1,"available":"1","held":"0","total":"1","locked":false},{"client":2,"available":"0.0","held":"2.5","total":"2.5","locked":false}]"#
+        );
+        assert_eq!(
+            ledger.to_deposit_log_csv().unwrap(),
+            "tx,client,amount,under_dispute\n1,1,1,false\n2,2,2.5,true\n"
+        );
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let mut ledger = AccountLedger::new();
+        ledger.append_many([
+            Transaction {
+                client: 1.into(),
+                tx: 1.into(),
+                tx_type: TransactionType::Deposit(AmountType::from_str_exact("1.2345").unwrap()),
+            },
+            Transaction {
+                client: 2.into(),
+                tx: 2.into(),
+                tx_type: TransactionType::Deposit(AmountType::from_str_exact("5").unwrap()),
+            },
+            Transaction {
+                client: 2.into(),
+                tx: 3.into(),
+                tx_type: TransactionType::Deposit(AmountType::from_str_exact("2").unwrap()),
+            },
+            Transaction {
+                client: 2.into(),
+                tx: 2.into(),
+                tx_type: TransactionType::Dispute,
+            },
+            Transaction {
+                client: 2.into(),
+                tx: 2.into(),
+                tx_type: TransactionType::Chargeback,
+            },
+            Transaction {
+                client: 1.into(),
+                tx: 1.into(),
+                tx_type: TransactionType::Dispute,
+            },
+        ]);
+
+        let mut restored = AccountLedger::from_snapshot(
+            &ledger.to_csv().unwrap(),
+            &ledger.to_deposit_log_csv().unwrap(),
+        )
+        .unwrap();
+        assert_client_state_equals(&snapshot_states(&restored), &snapshot_states(&ledger));
+        assert_eq!(restored.deposit_log, ledger.deposit_log);
+
+        // The restored ledger resumes where the original left off.
+        let resolve = Transaction {
+            client: 1.into(),
+            tx: 1.into(),
+            tx_type: TransactionType::Resolve,
+        };
+        ledger.append(resolve.clone());
+        restored.append(resolve);
+        assert_client_state_equals(&snapshot_states(&restored), &snapshot_states(&ledger));
+        assert_eq!(restored.deposit_log, ledger.deposit_log);
+
+        assert!(AccountLedger::from_snapshot("client,available\n1,abc\n", "").is_err());
+    }
+
+    #[test]
+    fn test_drain_pending() {
+        let mut ledger = AccountLedger::new();
+        ledger.append_many([
+            Transaction {
+                client: 1.into(),
+                tx: 1.into(),
+                tx_type: TransactionType::Deposit(AmountType::from_str_exact("1").unwrap()),
+            },
+            Transaction {
+                client: 1.into(),
+                tx: 1.into(),
+                tx_type: TransactionType::Dispute,
+            },
+            Transaction {
+                client: 1.into(),
+                tx: 1.into(),
+                tx_type: TransactionType::Chargeback,
+            },
+            Transaction {
+                client: 1.into(),
+                tx: 2.into(),
+                tx_type: TransactionType::Deposit(AmountType::from_str_exact("3").unwrap()),
+            },
+            Transaction {
+                client: 1.into(),
+                tx: 3.into(),
+                tx_type: TransactionType::Withdrawal(AmountType::from_str_exact("5").unwrap()),
+            },
+            Transaction {
+                client: 1.into(),
+                tx: 4.into(),
+                tx_type: TransactionType::Withdrawal(AmountType::from_str_exact("2").unwrap()),
+            },
+        ]);
+        assert_eq!(
+            ledger
+                .client_state(1.into())
+                .unwrap()
+                .pending_locked_transactions
+                .len(),
+            3
+        );
+
+        // The first withdrawal exceeds the balance and is ignored; the second one only succeeds after the deposit.
+        assert_eq!(ledger.drain_pending(1.into()), 2);
+        assert_client_state_equals(
+            &ledger.clients,
+            &HashMap::from([(
+                1.into(),
+                ClientState {
+                    available: AmountType::from_str_exact("1").unwrap(),
+                    #[cfg(feature = "detailed-stats")]
+                    deposit_count: 2,
+                    #[cfg(feature = "detailed-stats")]
+                    withdrawal_count: 1,
+                    #[cfg(feature = "detailed-stats")]
+                    dispute_count: 1,
+                    #[cfg(feature = "detailed-stats")]
+                    chargeback_count: 1,
+                    ..Default::default()
+                },
+            )]),
+        );
+        assert_eq!(
+            ledger.take_warnings(),
+            vec![
+                ProcessingWarning::ProcessedAfterUnlock {
+                    client: 1.into(),
+                    tx: 2.into(),
+                },
+                ProcessingWarning::ProcessedAfterUnlock {
+                    client: 1.into(),
+                    tx: 4.into(),
+                },
+            ]
+        );
+        assert_eq!(ledger.drain_pending(2.into()), 0);
+    }
+
+    #[test]
+    fn test_backfill_transactions() {
+        let deposit = |tx: u32, amount: &str| Transaction {
+            client: 1.into(),
+            tx: tx.into(),
+            tx_type: TransactionType::Deposit(AmountType::from_str_exact(amount).unwrap()),
+        };
+        let dispute = |tx: u32| Transaction {
+            client: 1.into(),
+            tx: tx.into(),
+            tx_type: TransactionType::Dispute,
+        };
+
+        let mut ledger = AccountLedger::with_history();
+        ledger.append_many([deposit(1, "1"), deposit(3, "3")]);
+
+        backfill_transactions(&mut ledger, [deposit(2, "2"), dispute(1)]).unwrap();
+        assert_client_state_equals(
+            &ledger.clients,
+            &HashMap::from([(
+                1.into(),
+                ClientState {
+                    available: AmountType::from_str_exact("5").unwrap(),
+                    held: AmountType::from_str_exact("1").unwrap(),
+                    #[cfg(feature = "detailed-stats")]
+                    deposit_count: 3,
+                    #[cfg(feature = "detailed-stats")]
+                    dispute_count: 1,
+                    ..Default::default()
+                },
+            )]),
+        );
+
+        // The withdrawal was applied, but the backfilled dispute comes first and leaves too little to cover it.
+        ledger.append(Transaction {
+            client: 1.into(),
+            tx: 5.into(),
+            tx_type: TransactionType::Withdrawal(AmountType::from_str_exact("5").unwrap()),
+        });
+        assert!(matches!(
+            backfill_transactions(&mut ledger, [dispute(3)]),
+            Err(ProcessingError::BackfillConflict(tx)) if tx == 5.into()
+        ));
+        assert_eq!(ledger.client_state(1.into()).unwrap().held, AmountType::ONE);
+
+        let mut restored = AccountLedger::from_snapshot(
+            &ledger.to_csv().unwrap(),
+            &ledger.to_deposit_log_csv().unwrap(),
+        )
+        .unwrap();
+        assert!(matches!(
+            backfill_transactions(&mut restored, [deposit(4, "4")]),
+            Err(ProcessingError::HistoryUnavailable)
+        ));
+        assert!(matches!(
+            backfill_transactions(&mut AccountLedger::new(), [deposit(4, "4")]),
+            Err(ProcessingError::HistoryUnavailable)
+        ));
+    }
+
+    #[test]
+    fn test_checkpoint() {
+        let mut ledger = AccountLedger::new();
+        ledger.append_many([
+            Transaction {
+                client: 1.into(),
+                tx: 1.into(),
+                tx_type: TransactionType::Deposit(AmountType::from_str_exact("1").unwrap()),
+            },
+            Transaction {
+                client: 1.into(),
+                tx: 2.into(),
+                tx_type: TransactionType::Deposit(AmountType::from_str_exact("2").unwrap()),
+            },
+            Transaction {
+                client: 1.into(),
+                tx: 2.into(),
+                tx_type: TransactionType::Dispute,
+            },
+        ]);
+
+        let checkpoint = ledger.checkpoint().unwrap();
+        assert_eq!(
+            checkpoint.deposit_log_csv,
+            "tx,client,amount,under_dispute\n2,1,2,true\n"
+        );
+        assert_eq!(ledger.deposit_log.len(), 1);
+
+        let restored =
+            AccountLedger::from_snapshot(&checkpoint.clients_csv, &checkpoint.deposit_log_csv)
+                .unwrap();
+        assert_client_state_equals(&snapshot_states(&restored), &snapshot_states(&ledger));
+        assert_eq!(restored.deposit_log, ledger.deposit_log);
+    }
+}
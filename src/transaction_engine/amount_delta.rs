@@ -0,0 +1,48 @@
+use super::AmountType;
+use std::fmt::{self, Display, Formatter};
+use std::ops::{Add, Sub};
+
+/// A signed change of a balance, as opposed to an amount of money, which the engine keeps non-negative.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct AmountDelta(AmountType);
+
+impl AmountDelta {
+    /// The change from `before` to `after`.
+    pub fn between(before: AmountType, after: AmountType) -> Self {
+        AmountDelta(after - before)
+    }
+}
+
+impl Display for AmountDelta {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Add for AmountDelta {
+    type Output = AmountDelta;
+
+    fn add(self, rhs: AmountDelta) -> Self::Output {
+        AmountDelta(self.0 + rhs.0)
+    }
+}
+
+impl Sub for AmountDelta {
+    type Output = AmountDelta;
+
+    fn sub(self, rhs: AmountDelta) -> Self::Output {
+        AmountDelta(self.0 - rhs.0)
+    }
+}
+
+impl From<AmountType> for AmountDelta {
+    fn from(amount: AmountType) -> Self {
+        AmountDelta(amount)
+    }
+}
+
+impl From<AmountDelta> for AmountType {
+    fn from(delta: AmountDelta) -> Self {
+        delta.0
+    }
+}
@@ -0,0 +1,54 @@
+use std::collections::VecDeque;
+use std::io::{self, Read};
+
+/// Reads each source until EOF, then moves on to the next, e.g. to process several files as one stream. For two
+/// sources of known types, `Read::chain` does the same without boxing.
+///
+/// The bytes are passed through as they are: each source should end with a newline, and only the first one should
+/// have a CSV header, since any later header is read as a malformed record.
+pub struct ChainedReader<'a> {
+    sources: VecDeque<Box<dyn Read + 'a>>,
+}
+
+impl<'a> ChainedReader<'a> {
+    pub fn new(sources: impl IntoIterator<Item = Box<dyn Read + 'a>>) -> Self {
+        Self {
+            sources: sources.into_iter().collect(),
+        }
+    }
+}
+
+impl Read for ChainedReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while let Some(source) = self.sources.front_mut() {
+            match source.read(buf)? {
+                0 if !buf.is_empty() => {
+                    self.sources.pop_front();
+                }
+                len => return Ok(len),
+            }
+        }
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{process_csv_transactions_and_return_csv_client_states, InputFormat};
+    use super::*;
+
+    #[test]
+    fn test_chained_reader() {
+        let reader = ChainedReader::new([
+            Box::new("type,client,tx,amount\ndeposit,1,1,1\n".as_bytes()) as Box<dyn Read>,
+            Box::new("".as_bytes()),
+            Box::new("deposit,1,2,2\n".as_bytes()),
+        ]);
+
+        assert_eq!(
+            process_csv_transactions_and_return_csv_client_states(reader, InputFormat::Csv)
+                .unwrap(),
+            "client,available,held,total,locked\n1,3,0,3,false\n"
+        );
+    }
+}
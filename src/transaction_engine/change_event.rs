@@ -0,0 +1,48 @@
+use super::{AmountDelta, AmountType, ClientId, ClientState};
+
+/// A change to an account caused by a transaction, e.g. to feed an event store or webhook notifications.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChangeEvent {
+    BalanceChanged {
+        client: ClientId,
+        delta_available: AmountDelta,
+        delta_held: AmountDelta,
+        new_available: AmountType,
+        new_held: AmountType,
+    },
+    ClientLocked {
+        client: ClientId,
+    },
+    /// Transaction processing never unlocks a client; this is for unlocks done out of band, like
+    /// `AccountLedger::drain_pending`.
+    ClientUnlocked {
+        client: ClientId,
+    },
+}
+
+impl ChangeEvent {
+    /// The events that describe the move of `client` from the `(available, held, locked)` it had `before` to `after`,
+    /// balances first.
+    pub(super) fn between(
+        client: ClientId,
+        (available_before, held_before, locked_before): (AmountType, AmountType, bool),
+        after: &ClientState,
+    ) -> Vec<Self> {
+        let mut events = Vec::new();
+        if (available_before, held_before) != (after.available, after.held) {
+            events.push(ChangeEvent::BalanceChanged {
+                client,
+                delta_available: AmountDelta::between(available_before, after.available),
+                delta_held: AmountDelta::between(held_before, after.held),
+                new_available: after.available,
+                new_held: after.held,
+            });
+        }
+        match (locked_before, after.locked) {
+            (false, true) => events.push(ChangeEvent::ClientLocked { client }),
+            (true, false) => events.push(ChangeEvent::ClientUnlocked { client }),
+            _ => {}
+        }
+        events
+    }
+}
@@ -0,0 +1,32 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display, Formatter};
+
+/// Displays as the bare number, which is also how it is read from and written to CSV. The alternate form (`{:#}`)
+/// is meant for humans, e.g. `client 42`.
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct ClientId(u16);
+
+impl Display for ClientId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            write!(f, "client {}", self.0)
+        } else {
+            write!(f, "{}", self.0)
+        }
+    }
+}
+
+impl From<u16> for ClientId {
+    fn from(id: u16) -> Self {
+        ClientId(id)
+    }
+}
+
+impl From<ClientId> for u16 {
+    fn from(id: ClientId) -> Self {
+        id.0
+    }
+}
@@ -0,0 +1,136 @@
+use super::{AmountType, ClientId, ClientState};
+use std::collections::HashMap;
+use std::ops::{Deref, DerefMut};
+
+/// Client states keyed by client id. Dereferences to the underlying `HashMap`, but iterating over a reference yields
+/// the entries sorted ascending by client id, for deterministic output.
+#[derive(Default, Debug, PartialEq)]
+pub struct ClientStateMap(HashMap<ClientId, ClientState>);
+
+impl ClientStateMap {
+    pub fn into_sorted_vec(self) -> Vec<(ClientId, ClientState)> {
+        let mut clients: Vec<_> = self.0.into_iter().collect();
+        clients.sort_unstable_by_key(|&(client_id, _)| client_id);
+        clients
+    }
+
+    pub fn total_available(&self) -> AmountType {
+        self.0
+            .values()
+            .map(|client_state| client_state.available)
+            .sum()
+    }
+
+    pub fn total_held(&self) -> AmountType {
+        self.0.values().map(|client_state| client_state.held).sum()
+    }
+
+    /// The sum of every client's total, i.e. `total_available() + total_held()`.
+    pub fn grand_total(&self) -> AmountType {
+        let grand_total = self
+            .0
+            .values()
+            .map(|client_state| client_state.available + client_state.held)
+            .sum();
+        debug_assert_eq!(self.total_available() + self.total_held(), grand_total);
+        grand_total
+    }
+}
+
+impl<'a> IntoIterator for &'a ClientStateMap {
+    type Item = (ClientId, &'a ClientState);
+    type IntoIter = std::vec::IntoIter<Self::Item>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        let mut clients: Vec<_> = self
+            .0
+            .iter()
+            .map(|(&client_id, client_state)| (client_id, client_state))
+            .collect();
+        clients.sort_unstable_by_key(|&(client_id, _)| client_id);
+        clients.into_iter()
+    }
+}
+
+impl Deref for ClientStateMap {
+    type Target = HashMap<ClientId, ClientState>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for ClientStateMap {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl From<HashMap<ClientId, ClientState>> for ClientStateMap {
+    fn from(clients: HashMap<ClientId, ClientState>) -> Self {
+        ClientStateMap(clients)
+    }
+}
+
+impl From<ClientStateMap> for HashMap<ClientId, ClientState> {
+    fn from(clients: ClientStateMap) -> Self {
+        clients.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sorted_iteration() {
+        let clients = ClientStateMap::from(
+            [3, 1, 2]
+                .map(|client_id| (ClientId::from(client_id), ClientState::default()))
+                .into_iter()
+                .collect::<HashMap<_, _>>(),
+        );
+
+        assert_eq!(
+            (&clients)
+                .into_iter()
+                .map(|(client_id, _)| client_id)
+                .collect::<Vec<_>>(),
+            [1, 2, 3].map(ClientId::from)
+        );
+        assert_eq!(
+            clients
+                .into_sorted_vec()
+                .into_iter()
+                .map(|(client_id, _)| client_id)
+                .collect::<Vec<_>>(),
+            [1, 2, 3].map(ClientId::from)
+        );
+    }
+
+    #[test]
+    fn test_totals() {
+        let clients = ClientStateMap::from(HashMap::from([
+            (
+                1.into(),
+                ClientState {
+                    available: AmountType::from_str_exact("1.5").unwrap(),
+                    held: AmountType::from_str_exact("2").unwrap(),
+                    ..Default::default()
+                },
+            ),
+            (
+                2.into(),
+                ClientState {
+                    available: AmountType::from_str_exact("-0.5").unwrap(),
+                    ..Default::default()
+                },
+            ),
+        ]));
+
+        assert_eq!(clients.total_available(), AmountType::ONE);
+        assert_eq!(clients.total_held(), AmountType::TWO);
+        assert_eq!(clients.grand_total(), AmountType::from(3));
+        assert_eq!(ClientStateMap::default().grand_total(), AmountType::ZERO);
+    }
+}
@@ -0,0 +1,687 @@
+use super::{
+    AmountType, ApplicationResult, ClientId, ClientState, ClientStateMap, DepositLog, FlowTotals,
+    Transaction, TransactionId, TransactionLog, TransactionType,
+};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientFlowReport {
+    pub client_id: ClientId,
+    pub total_deposited: AmountType,
+    pub total_withdrawn: AmountType,
+    /// `total_deposited - total_withdrawn`
+    pub net_flow: AmountType,
+    pub current_available: AmountType,
+}
+
+/// One report per client in `clients`, sorted by net flow descending. The totals are the ones returned by
+/// `process_transactions_and_return_flow_totals`; clients without an entry are taken to have moved nothing.
+pub fn net_flow_report(
+    clients: &ClientStateMap,
+    deposit_totals: &FlowTotals,
+    withdrawal_totals: &FlowTotals,
+) -> Vec<ClientFlowReport> {
+    let mut reports: Vec<_> = clients
+        .into_iter()
+        .map(|(client_id, client_state)| {
+            let total_deposited = deposit_totals.get(&client_id).copied().unwrap_or_default();
+            let total_withdrawn = withdrawal_totals
+                .get(&client_id)
+                .copied()
+                .unwrap_or_default();
+            ClientFlowReport {
+                client_id,
+                total_deposited,
+                total_withdrawn,
+                net_flow: total_deposited - total_withdrawn,
+                current_available: client_state.available,
+            }
+        })
+        .collect();
+    reports.sort_by_key(|report| Reverse(report.net_flow));
+    reports
+}
+
+/// A client locked by a chargeback, with the chargeback that locked it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LockedAccountInfo {
+    pub client_id: ClientId,
+    pub chargeback_tx_id: TransactionId,
+    pub chargeback_amount: AmountType,
+    pub client_state: ClientState,
+}
+
+/// A client with open disputes at the end of processing, for compliance review.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisputeSummary {
+    pub client: ClientId,
+    /// The disputed deposits, by transaction id.
+    pub open_disputes: Vec<TransactionId>,
+    pub total_held: AmountType,
+}
+
+/// How many clients are frozen, and how much money is tied up in their held balances.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct LockStatusSummary {
+    pub locked_count: usize,
+    pub unlocked_count: usize,
+    pub locked_total_held: AmountType,
+    pub unlocked_total_available: AmountType,
+}
+
+pub fn summarize_by_lock_status(clients: &HashMap<ClientId, ClientState>) -> LockStatusSummary {
+    let mut summary = LockStatusSummary::default();
+    for client_state in clients.values() {
+        if client_state.locked {
+            summary.locked_count += 1;
+            summary.locked_total_held += client_state.held;
+        } else {
+            summary.unlocked_count += 1;
+            summary.unlocked_total_available += client_state.available;
+        }
+    }
+    summary
+}
+
+/// Splits the client states into `(locked, unlocked)`, moving them rather than cloning.
+pub fn partition_clients_by_status(
+    clients: HashMap<ClientId, ClientState>,
+) -> (
+    HashMap<ClientId, ClientState>,
+    HashMap<ClientId, ClientState>,
+) {
+    clients
+        .into_iter()
+        .partition(|(_, client_state)| client_state.locked)
+}
+
+/// Same as `partition_clients_by_status`, but borrows the client states.
+pub fn partition_clients_by_status_ref(
+    clients: &HashMap<ClientId, ClientState>,
+) -> (
+    HashMap<ClientId, &ClientState>,
+    HashMap<ClientId, &ClientState>,
+) {
+    clients
+        .iter()
+        .map(|(&client_id, client_state)| (client_id, client_state))
+        .partition(|(_, client_state)| client_state.locked)
+}
+
+/// The clients with nothing available or held, as `(locked, unlocked)`, each sorted by id. Locked ones were typically
+/// charged back in full, unlocked ones withdrew exactly what they deposited.
+pub fn zeroed_out_clients(
+    clients: &HashMap<ClientId, ClientState>,
+) -> (Vec<ClientId>, Vec<ClientId>) {
+    let (mut locked, mut unlocked) = (Vec::new(), Vec::new());
+    for (&client_id, client_state) in clients {
+        if client_state.available.is_zero() && client_state.held.is_zero() {
+            if client_state.locked {
+                locked.push(client_id);
+            } else {
+                unlocked.push(client_id);
+            }
+        }
+    }
+    locked.sort_unstable();
+    unlocked.sort_unstable();
+    (locked, unlocked)
+}
+
+/// The share of `client_id`'s deposits that were charged back, e.g. to raise a fraud alert above 0.01. Only applied
+/// transactions count, as recorded by `process_transactions_and_return_client_states_with_log`. 0 for a client without
+/// deposits.
+pub fn chargeback_rate(client_id: ClientId, log: &TransactionLog) -> f64 {
+    chargeback_rates(log)
+        .get(&client_id)
+        .copied()
+        .unwrap_or_default()
+}
+
+/// The clients whose applied chargebacks exceed `threshold` of their applied deposits, sorted by id.
+pub fn clients_exceeding_chargeback_rate(log: &TransactionLog, threshold: f64) -> Vec<ClientId> {
+    let mut client_ids: Vec<_> = chargeback_rates(log)
+        .into_iter()
+        .filter(|&(_, rate)| threshold < rate)
+        .map(|(client_id, _)| client_id)
+        .collect();
+    client_ids.sort_unstable();
+    client_ids
+}
+
+/// The chargeback rate of every client with applied deposits in `log`.
+fn chargeback_rates(log: &TransactionLog) -> HashMap<ClientId, f64> {
+    let mut counts = HashMap::<ClientId, (u32, u32)>::new();
+    for (transaction, _) in log
+        .entries()
+        .iter()
+        .filter(|(_, result)| *result == ApplicationResult::Applied)
+    {
+        let (deposits, chargebacks) = counts.entry(transaction.client).or_default();
+        match transaction.tx_type {
+            TransactionType::Deposit(_) => *deposits += 1,
+            TransactionType::Chargeback => *chargebacks += 1,
+            _ => {}
+        }
+    }
+    counts
+        .into_iter()
+        .filter(|&(_, (deposits, _))| 0 < deposits)
+        .map(|(client_id, (deposits, chargebacks))| {
+            (client_id, f64::from(chargebacks) / f64::from(deposits))
+        })
+        .collect()
+}
+
+/// How two deposit logs differ, found by `diff_deposit_logs`. Each list is sorted by transaction id.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DepositLogDiff {
+    pub only_in_left: Vec<TransactionId>,
+    pub only_in_right: Vec<TransactionId>,
+    pub different_client: Vec<TransactionId>,
+    pub different_amount: Vec<TransactionId>,
+    pub different_dispute_status: Vec<TransactionId>,
+}
+
+/// Compares two deposit logs that should match, e.g. from two systems being reconciled. A deposit in both logs is
+/// listed once for each field that differs. Amounts that differ only in scale, like `1.0` and `1.00`, are equal.
+pub fn diff_deposit_logs(left: &DepositLog, right: &DepositLog) -> DepositLogDiff {
+    let mut diff = DepositLogDiff {
+        only_in_right: right
+            .keys()
+            .filter(|tx| !left.contains_key(tx))
+            .copied()
+            .collect(),
+        ..Default::default()
+    };
+    for (&tx, &(left_client, left_amount, left_under_dispute)) in left {
+        let Some(&(right_client, right_amount, right_under_dispute)) = right.get(&tx) else {
+            diff.only_in_left.push(tx);
+            continue;
+        };
+        if left_client != right_client {
+            diff.different_client.push(tx);
+        }
+        if left_amount != right_amount {
+            diff.different_amount.push(tx);
+        }
+        if left_under_dispute != right_under_dispute {
+            diff.different_dispute_status.push(tx);
+        }
+    }
+    for txs in [
+        &mut diff.only_in_left,
+        &mut diff.only_in_right,
+        &mut diff.different_client,
+        &mut diff.different_amount,
+        &mut diff.different_dispute_status,
+    ] {
+        txs.sort_unstable();
+    }
+    diff
+}
+
+/// A client with a negative balance, found by `verify_no_negative_balances`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NegativeBalanceReport {
+    pub client_id: ClientId,
+    pub available: AmountType,
+    pub held: AmountType,
+}
+
+/// Checks the invariant that no balance is negative, sorted by client id on failure. The engine never lets
+/// transactions overdraw a client, so a violation is a bug rather than bad input; the exception is a forced
+/// correction, which may make `available` negative on purpose.
+pub fn verify_no_negative_balances(
+    clients: &HashMap<ClientId, ClientState>,
+) -> Result<(), Vec<NegativeBalanceReport>> {
+    let mut reports: Vec<_> = clients
+        .iter()
+        .filter(|(_, client_state)| {
+            client_state.available < AmountType::ZERO || client_state.held < AmountType::ZERO
+        })
+        .map(|(&client_id, client_state)| NegativeBalanceReport {
+            client_id,
+            available: client_state.available,
+            held: client_state.held,
+        })
+        .collect();
+    if reports.is_empty() {
+        return Ok(());
+    }
+    reports.sort_unstable_by_key(|report| report.client_id);
+    Err(reports)
+}
+
+/// The total held across all clients, i.e. the funds under dispute, for balance sheet reconciliation.
+pub fn total_under_dispute(clients: &HashMap<ClientId, ClientState>) -> AmountType {
+    clients.values().map(|client_state| client_state.held).sum()
+}
+
+/// How many clients have funds under dispute.
+pub fn clients_under_dispute_count(clients: &HashMap<ClientId, ClientState>) -> usize {
+    clients
+        .values()
+        .filter(|client_state| AmountType::ZERO < client_state.held)
+        .count()
+}
+
+/// A one-sentence account of what `tx` did to its client, for verbose output and audit reports, e.g.
+/// `Deposit of 5.4321 for client 3 (tx=10): available increased from 0.0000 to 5.4321`. Amounts are shown with four
+/// decimal places.
+pub fn explain_transaction(
+    tx: &Transaction,
+    state_before: &ClientState,
+    state_after: &ClientState,
+    was_applied: bool,
+) -> String {
+    let client = tx.client;
+    let id = tx.tx;
+    let subject = match tx.tx_type {
+        TransactionType::Deposit(amount) => {
+            format!("Deposit of {amount:.4} for {client:#} (tx={id})")
+        }
+        TransactionType::Withdrawal(amount) => {
+            format!("Withdrawal of {amount:.4} for {client:#} (tx={id})")
+        }
+        TransactionType::Dispute => format!("Dispute on tx={id} for {client:#}"),
+        TransactionType::Resolve => format!("Resolve of tx={id} for {client:#}"),
+        TransactionType::Chargeback => format!("Chargeback of tx={id} for {client:#}"),
+        TransactionType::Correction(amount, _) => {
+            format!("Correction of {amount:+.4} for {client:#} (tx={id})")
+        }
+    };
+    let available_before = state_before.available;
+    let available_after = state_after.available;
+
+    if !was_applied {
+        let detail = match tx.tx_type {
+            _ if state_before.locked => " (the account is locked)".to_owned(),
+            TransactionType::Withdrawal(amount) if available_before < amount => {
+                format!(" (available was {available_before:.4}, insufficient)")
+            }
+            _ => String::new(),
+        };
+        return format!("{subject}: ignored{detail}");
+    }
+    let held_change = (state_after.held - state_before.held).abs();
+    match tx.tx_type {
+        TransactionType::Deposit(_) => format!(
+            "{subject}: available increased from {available_before:.4} to {available_after:.4}"
+        ),
+        TransactionType::Withdrawal(_) => format!(
+            "{subject}: available decreased from {available_before:.4} to {available_after:.4}"
+        ),
+        TransactionType::Correction(..) => format!(
+            "{subject}: available changed from {available_before:.4} to {available_after:.4}"
+        ),
+        TransactionType::Dispute => {
+            format!("{subject}: {held_change:.4} moved from available to held")
+        }
+        TransactionType::Resolve => {
+            format!("{subject}: {held_change:.4} moved from held back to available")
+        }
+        TransactionType::Chargeback => {
+            format!("{subject}: {held_change:.4} removed from held and the account locked")
+        }
+    }
+}
+
+/// A one-line summary of a client for operators, e.g.
+/// `Client 42: available=$1,234.5678  held=$0.0000  total=$1,234.5678  status=OK`. With `color`, `LOCKED` is
+/// printed in red using ANSI escape codes.
+pub fn client_summary_line(client_id: ClientId, client_state: &ClientState, color: bool) -> String {
+    let status = match (client_state.locked, color) {
+        (false, _) => "OK",
+        (true, false) => "LOCKED",
+        (true, true) => "\x1b[31mLOCKED\x1b[0m",
+    };
+    format!(
+        "Client {client_id}: available={}  held={}  total={}  status={status}",
+        format_currency(client_state.available),
+        format_currency(client_state.held),
+        format_currency(client_state.available + client_state.held),
+    )
+}
+
+/// `$` and four decimal places, with commas separating the thousands, e.g. `-$1,234.5000`.
+fn format_currency(amount: AmountType) -> String {
+    let formatted = format_amount(amount.abs(), 4, ',', '.');
+    let sign = if amount.is_sign_negative() && !amount.is_zero() {
+        "-"
+    } else {
+        ""
+    };
+    format!("{sign}${formatted}")
+}
+
+/// Rounds `amount` to `decimal_places` and groups the integer digits by thousands, e.g. `1,234,567.8901` with the US
+/// separators `','` and `'.'`, or `1.234.567,8901` with the European `'.'` and `','`.
+pub fn format_amount(
+    amount: AmountType,
+    decimal_places: u32,
+    thousands_sep: char,
+    decimal_sep: char,
+) -> String {
+    let amount = amount.round_dp(decimal_places);
+    let digits = format!("{:.*}", decimal_places as usize, amount.abs());
+    let (integer, fraction) = digits.split_once('.').unwrap_or((&digits, ""));
+    let mut formatted = String::new();
+    if amount.is_sign_negative() && !amount.is_zero() {
+        formatted.push('-');
+    }
+    for (i, digit) in integer.chars().enumerate() {
+        if 0 < i && (integer.len() - i) % 3 == 0 {
+            formatted.push(thousands_sep);
+        }
+        formatted.push(digit);
+    }
+    if !fraction.is_empty() {
+        formatted.push(decimal_sep);
+        formatted.push_str(fraction);
+    }
+    formatted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{
+        process_transactions_and_return_client_states_with_log,
+        process_transactions_and_return_flow_totals, Transaction, TransactionType::*,
+    };
+    use super::*;
+
+    #[test]
+    fn test_net_flow_report() {
+        let amount = |s| AmountType::from_str_exact(s).unwrap();
+        let (clients, deposit_totals, withdrawal_totals) =
+            process_transactions_and_return_flow_totals([
+                Transaction {
+                    client: 1.into(),
+                    tx: 1.into(),
+                    tx_type: Deposit(amount("10")),
+                },
+                Transaction {
+                    client: 1.into(),
+                    tx: 2.into(),
+                    tx_type: Withdrawal(amount("9")),
+                },
+                Transaction {
+                    client: 2.into(),
+                    tx: 3.into(),
+                    tx_type: Deposit(amount("5")),
+                },
+                Transaction {
+                    client: 2.into(),
+                    tx: 4.into(),
+                    tx_type: Withdrawal(amount("50")),
+                },
+            ]);
+
+        assert_eq!(
+            net_flow_report(&clients, &deposit_totals, &withdrawal_totals),
+            vec![
+                ClientFlowReport {
+                    client_id: 2.into(),
+                    total_deposited: amount("5"),
+                    total_withdrawn: AmountType::ZERO,
+                    net_flow: amount("5"),
+                    current_available: amount("5"),
+                },
+                ClientFlowReport {
+                    client_id: 1.into(),
+                    total_deposited: amount("10"),
+                    total_withdrawn: amount("9"),
+                    net_flow: amount("1"),
+                    current_available: amount("1"),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_client_summary_line() {
+        let client_state = ClientState {
+            available: AmountType::from_str_exact("1234.5678").unwrap(),
+            held: AmountType::from_str_exact("1000000").unwrap(),
+            locked: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            client_summary_line(42.into(), &client_state, false),
+            "Client 42: available=$1,234.5678  held=$1,000,000.0000  total=$1,001,234.5678  status=LOCKED"
+        );
+        assert!(
+            client_summary_line(42.into(), &client_state, true).ends_with("\x1b[31mLOCKED\x1b[0m")
+        );
+
+        let client_state = ClientState {
+            available: AmountType::from_str_exact("-12.5").unwrap(),
+            ..Default::default()
+        };
+        assert_eq!(
+            client_summary_line(7.into(), &client_state, false),
+            "Client 7: available=-$12.5000  held=$0.0000  total=-$12.5000  status=OK"
+        );
+    }
+
+    #[test]
+    fn test_format_amount() {
+        let amount = AmountType::from_str_exact("1234567.8901").unwrap();
+        assert_eq!(format_amount(amount, 4, ',', '.'), "1,234,567.8901");
+        assert_eq!(format_amount(amount, 2, '.', ','), "1.234.567,89");
+        assert_eq!(format_amount(-amount, 0, ',', '.'), "-1,234,568");
+        assert_eq!(
+            format_amount(AmountType::from_str_exact("-0.001").unwrap(), 2, ',', '.'),
+            "0.00"
+        );
+        assert_eq!(format_amount(123.into(), 1, ',', '.'), "123.0");
+    }
+
+    #[test]
+    fn test_under_dispute() {
+        let clients = HashMap::from([
+            (
+                1.into(),
+                ClientState {
+                    available: 5.into(),
+                    held: 2.into(),
+                    ..Default::default()
+                },
+            ),
+            (
+                2.into(),
+                ClientState {
+                    held: AmountType::from_str_exact("1.5").unwrap(),
+                    locked: true,
+                    ..Default::default()
+                },
+            ),
+            (
+                3.into(),
+                ClientState {
+                    available: 1.into(),
+                    ..Default::default()
+                },
+            ),
+        ]);
+
+        assert_eq!(
+            total_under_dispute(&clients),
+            AmountType::from_str_exact("3.5").unwrap()
+        );
+        assert_eq!(clients_under_dispute_count(&clients), 2);
+    }
+
+    #[test]
+    fn test_partition_clients_by_status() {
+        let clients = HashMap::from([
+            (
+                1.into(),
+                ClientState {
+                    locked: true,
+                    ..Default::default()
+                },
+            ),
+            (2.into(), ClientState::default()),
+            (3.into(), ClientState::default()),
+        ]);
+
+        let (locked, unlocked) = partition_clients_by_status_ref(&clients);
+        assert_eq!(locked.keys().collect::<Vec<_>>(), [&1.into()]);
+        assert_eq!(unlocked.len(), 2);
+
+        let (locked, unlocked) = partition_clients_by_status(clients);
+        assert_eq!(locked.keys().collect::<Vec<_>>(), [&1.into()]);
+        assert!(unlocked.contains_key(&2.into()) && unlocked.contains_key(&3.into()));
+    }
+
+    #[test]
+    fn test_explain_transaction() {
+        let state = |available: &str, held: &str| ClientState {
+            available: AmountType::from_str_exact(available).unwrap(),
+            held: AmountType::from_str_exact(held).unwrap(),
+            ..Default::default()
+        };
+        let transaction = |tx: u32, tx_type| Transaction {
+            client: 3.into(),
+            tx: tx.into(),
+            tx_type,
+        };
+
+        assert_eq!(
+            explain_transaction(
+                &transaction(10, Deposit(AmountType::from_str_exact("5.4321").unwrap())),
+                &state("0", "0"),
+                &state("5.4321", "0"),
+                true
+            ),
+            "Deposit of 5.4321 for client 3 (tx=10): available increased from 0.0000 to 5.4321"
+        );
+        assert_eq!(
+            explain_transaction(
+                &transaction(2, Dispute),
+                &state("3", "0"),
+                &state("0.6544", "2.3456"),
+                true
+            ),
+            "Dispute on tx=2 for client 3: 2.3456 moved from available to held"
+        );
+        assert_eq!(
+            explain_transaction(
+                &transaction(11, Withdrawal(1.into())),
+                &state("0.3456", "0"),
+                &state("0.3456", "0"),
+                false
+            ),
+            "Withdrawal of 1.0000 for client 3 (tx=11): ignored (available was 0.3456, insufficient)"
+        );
+        assert_eq!(
+            explain_transaction(
+                &transaction(12, Correction(2.into(), false)),
+                &state("1", "0"),
+                &state("3", "0"),
+                true
+            ),
+            "Correction of +2.0000 for client 3 (tx=12): available changed from 1.0000 to 3.0000"
+        );
+    }
+
+    #[test]
+    fn test_chargeback_rate() {
+        let deposit = |client: u16, tx: u32| Transaction {
+            client: client.into(),
+            tx: tx.into(),
+            tx_type: Deposit(1.into()),
+        };
+        let (_, log) = process_transactions_and_return_client_states_with_log([
+            deposit(1, 1),
+            deposit(1, 2),
+            deposit(1, 3),
+            deposit(2, 4),
+            Transaction {
+                client: 1.into(),
+                tx: 1.into(),
+                tx_type: Dispute,
+            },
+            Transaction {
+                client: 1.into(),
+                tx: 1.into(),
+                tx_type: Chargeback,
+            },
+            // Ignored, since the deposit isn't under dispute, so it doesn't count.
+            Transaction {
+                client: 2.into(),
+                tx: 4.into(),
+                tx_type: Chargeback,
+            },
+        ]);
+
+        assert_eq!(chargeback_rate(1.into(), &log), 1.0 / 3.0);
+        assert_eq!(chargeback_rate(2.into(), &log), 0.0);
+        assert_eq!(chargeback_rate(3.into(), &log), 0.0);
+        assert_eq!(clients_exceeding_chargeback_rate(&log, 0.01), [1.into()]);
+    }
+
+    #[test]
+    fn test_verify_no_negative_balances() {
+        let mut clients = HashMap::from([(
+            1.into(),
+            ClientState {
+                available: 1.into(),
+                ..Default::default()
+            },
+        )]);
+        assert_eq!(verify_no_negative_balances(&clients), Ok(()));
+
+        clients.insert(
+            2.into(),
+            ClientState {
+                held: (-1).into(),
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            verify_no_negative_balances(&clients),
+            Err(vec![NegativeBalanceReport {
+                client_id: 2.into(),
+                available: AmountType::ZERO,
+                held: (-1).into(),
+            }])
+        );
+    }
+
+    #[test]
+    fn test_diff_deposit_logs() {
+        let left = DepositLog::from([
+            (1.into(), (1.into(), 5.into(), false)),
+            (2.into(), (1.into(), 5.into(), false)),
+            (3.into(), (2.into(), 7.into(), true)),
+            (
+                4.into(),
+                (2.into(), AmountType::from_str_exact("1.0").unwrap(), false),
+            ),
+        ]);
+        let right = DepositLog::from([
+            (2.into(), (2.into(), 6.into(), false)),
+            (3.into(), (2.into(), 7.into(), false)),
+            (
+                4.into(),
+                (2.into(), AmountType::from_str_exact("1.00").unwrap(), false),
+            ),
+            (5.into(), (3.into(), 1.into(), false)),
+        ]);
+
+        assert_eq!(
+            diff_deposit_logs(&left, &right),
+            DepositLogDiff {
+                only_in_left: vec![1.into()],
+                only_in_right: vec![5.into()],
+                different_client: vec![2.into()],
+                different_amount: vec![2.into()],
+                different_dispute_status: vec![3.into()],
+            }
+        );
+    }
+}
@@ -0,0 +1,132 @@
+use super::{AmountType, ClientId, ClientState, ProcessingError};
+use csv::{ReaderBuilder, Trim};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::io::Read;
+
+#[derive(Debug, Deserialize)]
+struct CorrectionCsvRecord {
+    client: ClientId,
+    available_adjustment: Option<AmountType>,
+    held_adjustment: Option<AmountType>,
+    #[serde(default)]
+    unlock: bool,
+    /// Apply the adjustments even if a balance becomes negative; the column may be absent altogether.
+    #[serde(default)]
+    force: bool,
+}
+
+/// What became of one row of a corrections CSV, see `apply_corrections`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CorrectionResult {
+    pub client_id: ClientId,
+    pub applied: bool,
+    /// The balances after the row, unchanged if it was rejected.
+    pub available: AmountType,
+    pub held: AmountType,
+}
+
+/// Adjusts the client states as an operator's corrections CSV says, outside of the transaction lifecycle. The columns
+/// are `client`, `available_adjustment` and `held_adjustment`, which may be empty for no adjustment, `unlock`, and an
+/// optional `force`. A row that would make either balance negative is rejected as a whole, unless `force` is true.
+/// Locked clients are adjusted too, and unknown clients start from an empty state; they are only added to `clients` by
+/// a row that is applied. Fails on the first malformed row, after applying the ones before it.
+pub fn apply_corrections(
+    clients: &mut HashMap<ClientId, ClientState>,
+    corrections_csv: impl Read,
+) -> Result<Vec<CorrectionResult>, ProcessingError> {
+    let mut reader = ReaderBuilder::new()
+        .trim(Trim::All)
+        .from_reader(corrections_csv);
+    let mut results = Vec::new();
+    for record in reader.deserialize() {
+        let record: CorrectionCsvRecord = record?;
+        let (available_before, held_before) = clients
+            .get(&record.client)
+            .map_or((AmountType::ZERO, AmountType::ZERO), |client_state| {
+                (client_state.available, client_state.held)
+            });
+        let available = available_before + record.available_adjustment.unwrap_or_default();
+        let held = held_before + record.held_adjustment.unwrap_or_default();
+        let applied = record.force || (AmountType::ZERO <= available && AmountType::ZERO <= held);
+        if applied {
+            eprintln!(
+                "warning: client {} corrected outside of the transaction lifecycle",
+                record.client
+            );
+            let client_state = clients.entry(record.client).or_default();
+            client_state.available = available;
+            client_state.held = held;
+            if record.unlock {
+                client_state.locked = false;
+            }
+        }
+        let (available, held) = if applied {
+            (available, held)
+        } else {
+            (available_before, held_before)
+        };
+        results.push(CorrectionResult {
+            client_id: record.client,
+            applied,
+            available,
+            held,
+        });
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_corrections() {
+        let mut clients = HashMap::from([(
+            ClientId::from(1),
+            ClientState {
+                available: 5.into(),
+                held: 2.into(),
+                locked: true,
+                ..Default::default()
+            },
+        )]);
+        let corrections = "client, available_adjustment, held_adjustment, unlock, force\n\
+                           1, 2, -2, true, false\n\
+                           1, -8, , false, false\n\
+                           2, -1, , false, true\n\
+                           3, -1, , false, false\n";
+
+        assert_eq!(
+            apply_corrections(&mut clients, corrections.as_bytes()).unwrap(),
+            [
+                CorrectionResult {
+                    client_id: 1.into(),
+                    applied: true,
+                    available: 7.into(),
+                    held: 0.into(),
+                },
+                CorrectionResult {
+                    client_id: 1.into(),
+                    applied: false,
+                    available: 7.into(),
+                    held: 0.into(),
+                },
+                CorrectionResult {
+                    client_id: 2.into(),
+                    applied: true,
+                    available: (-1).into(),
+                    held: 0.into(),
+                },
+                CorrectionResult {
+                    client_id: 3.into(),
+                    applied: false,
+                    available: 0.into(),
+                    held: 0.into(),
+                },
+            ]
+        );
+        assert!(!clients[&ClientId::from(1)].locked);
+        assert!(!clients.contains_key(&ClientId::from(3)));
+    }
+}
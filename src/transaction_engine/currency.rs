@@ -0,0 +1,71 @@
+use super::{AmountType, ClientId, ClientState};
+use std::collections::HashMap;
+
+/// Converts the balances of the clients in currency `from` to currency `to`, multiplying them by `rate` and rounding
+/// to four decimal places. Clients without a currency are taken to be in `from`; clients in another currency are left
+/// as they are.
+pub fn apply_exchange_rate(
+    clients: HashMap<ClientId, ClientState>,
+    from: &str,
+    to: &str,
+    rate: AmountType,
+) -> HashMap<ClientId, ClientState> {
+    clients
+        .into_iter()
+        .map(|(client_id, mut client_state)| {
+            if client_state.currency.as_deref().unwrap_or(from) == from {
+                client_state.available = (client_state.available * rate).round_dp(4);
+                client_state.held = (client_state.held * rate).round_dp(4);
+                client_state.currency = Some(to.to_owned());
+            } else {
+                eprintln!("client {client_id} is not in {from}, so it is not converted");
+            }
+            (client_id, client_state)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_exchange_rate() {
+        let clients = HashMap::from([
+            (
+                ClientId::from(1),
+                ClientState {
+                    available: AmountType::from_str_exact("10.5").unwrap(),
+                    held: 2.into(),
+                    ..Default::default()
+                },
+            ),
+            (
+                ClientId::from(2),
+                ClientState {
+                    available: 10.into(),
+                    currency: Some("GBP".to_owned()),
+                    ..Default::default()
+                },
+            ),
+        ]);
+
+        let clients = apply_exchange_rate(
+            clients,
+            "USD",
+            "EUR",
+            AmountType::from_str_exact("0.92345").unwrap(),
+        );
+        let converted = &clients[&ClientId::from(1)];
+        assert_eq!(
+            converted.available,
+            AmountType::from_str_exact("9.6962").unwrap()
+        );
+        assert_eq!(
+            converted.held,
+            AmountType::from_str_exact("1.8469").unwrap()
+        );
+        assert_eq!(converted.currency.as_deref(), Some("EUR"));
+        assert_eq!(clients[&ClientId::from(2)].available, 10.into());
+    }
+}
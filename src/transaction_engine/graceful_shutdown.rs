@@ -0,0 +1,58 @@
+use super::{AccountLedger, ProcessingError};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Makes SIGINT save a checkpoint of `ledger` to `save_path` and exit, instead of losing the state of a long-running
+/// stream. The checkpoint is written as JSON; deserialize it into an `AccountLedgerCheckpoint` and pass its fields to
+/// `AccountLedger::from_snapshot` to resume. Only one handler can be installed per process.
+pub fn install_shutdown_handler(
+    ledger: Arc<Mutex<AccountLedger>>,
+    save_path: PathBuf,
+) -> Result<(), ProcessingError> {
+    ctrlc::set_handler(move || {
+        let exit_code = match save_checkpoint(&mut ledger.lock().unwrap(), &save_path) {
+            Ok(()) => 130,
+            Err(e) => {
+                eprintln!("failed to save the ledger to {}: {e}", save_path.display());
+                1
+            }
+        };
+        std::process::exit(exit_code);
+    })?;
+    Ok(())
+}
+
+fn save_checkpoint(ledger: &mut AccountLedger, save_path: &Path) -> Result<(), ProcessingError> {
+    let checkpoint = ledger.checkpoint()?;
+    fs::write(save_path, serde_json::to_vec(&checkpoint)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{AccountLedgerCheckpoint, Transaction, TransactionType};
+    use super::*;
+
+    #[test]
+    fn test_save_checkpoint() {
+        let mut ledger = AccountLedger::new();
+        ledger.append(Transaction {
+            client: 1.into(),
+            tx: 1.into(),
+            tx_type: TransactionType::Deposit(2.into()),
+        });
+        let save_path =
+            std::env::temp_dir().join(format!("transaction_engine_{}.json", std::process::id()));
+
+        save_checkpoint(&mut ledger, &save_path).unwrap();
+        let checkpoint: AccountLedgerCheckpoint =
+            serde_json::from_slice(&fs::read(&save_path).unwrap()).unwrap();
+        let restored =
+            AccountLedger::from_snapshot(&checkpoint.clients_csv, &checkpoint.deposit_log_csv)
+                .unwrap();
+        assert_eq!(restored.to_csv().unwrap(), ledger.to_csv().unwrap());
+
+        fs::remove_file(&save_path).unwrap();
+    }
+}
@@ -0,0 +1,40 @@
+use std::fmt::{self, Display, Formatter};
+
+/// Why a transaction was ignored. `Display` gives the message logged to stderr.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IgnoreReason {
+    ClientLocked,
+    NotEnoughAvailableFunds,
+    /// A dispute referenced a transaction with no deposit on record.
+    NoDepositFound,
+    /// A resolve or chargeback referenced a transaction with no deposit on record.
+    NoDisputeFound,
+    AlreadyUnderDispute,
+    NotUnderDispute,
+    DisputeClientMismatch,
+    ResolveClientMismatch,
+    ChargebackClientMismatch,
+    NotEnoughAvailableFundsToDispute,
+    CorrectionWouldBeNegative,
+    /// The amount doesn't have the decimal places required by the `StrictAmountValidator`.
+    AmountScaleMismatch,
+}
+
+impl Display for IgnoreReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            IgnoreReason::ClientLocked => "client is locked",
+            IgnoreReason::NotEnoughAvailableFunds => "not enough available funds",
+            IgnoreReason::NoDepositFound => "no previous deposit transaction found",
+            IgnoreReason::NoDisputeFound => "no previous dispute transaction found",
+            IgnoreReason::AlreadyUnderDispute => "already under dispute",
+            IgnoreReason::NotUnderDispute => "not under dispute",
+            IgnoreReason::DisputeClientMismatch => "the client who files the dispute is different from the one who made the deposit",
+            IgnoreReason::ResolveClientMismatch => "the client who files the resolve is different from the one who filed the dispute",
+            IgnoreReason::ChargebackClientMismatch => "the client who files the chargeback is different from the one who filed the dispute",
+            IgnoreReason::NotEnoughAvailableFundsToDispute => "can't file this dispute due to not enough available funds",
+            IgnoreReason::CorrectionWouldBeNegative => "correction would produce a negative available balance",
+            IgnoreReason::AmountScaleMismatch => "amount doesn't have the required number of decimal places",
+        })
+    }
+}
@@ -0,0 +1,326 @@
+use super::TransactionType::Correction;
+use super::{AmountType, ClientId, InputCsvRecord, ProcessingError, Transaction, TransactionId};
+use csv::{ReaderBuilder, StringRecord, Trim, Writer};
+use serde::Serialize;
+use serde_json::Value;
+use std::cell::Cell;
+use std::io::{BufRead, BufReader, Chain, Cursor, Read, Write};
+use std::rc::Rc;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum InputFormat {
+    #[default]
+    Csv,
+    /// Either a stream of JSON objects (NDJSON) or a single JSON array of objects. An array is loaded into memory at
+    /// once, so prefer NDJSON for large inputs.
+    Json,
+    /// Decided by `auto_detect_format`.
+    Auto,
+}
+
+/// The whole input given to `auto_detect_format`, with the whitespace it read past put back in front.
+pub type SniffedReader<R> = Chain<Cursor<Vec<u8>>, BufReader<R>>;
+
+/// Looks at the first non-whitespace byte: `{` or `[` means JSON, anything else CSV. Returns the format along with a
+/// reader of the whole input, from which nothing has been consumed: only the buffer of `reader` is inspected, and in
+/// case it holds nothing but whitespace, the whitespace read past is put back in front of the rest.
+pub fn auto_detect_format<R: Read>(
+    mut reader: BufReader<R>,
+) -> std::io::Result<(InputFormat, SniffedReader<R>)> {
+    let mut whitespace = Vec::new();
+    let format = loop {
+        let buffer = reader.fill_buf()?;
+        match buffer.iter().find(|byte| !byte.is_ascii_whitespace()) {
+            Some(b'{' | b'[') => break InputFormat::Json,
+            Some(_) => break InputFormat::Csv,
+            None if buffer.is_empty() => break InputFormat::Csv,
+            None => {
+                whitespace.extend_from_slice(buffer);
+                let whitespace_len = buffer.len();
+                reader.consume(whitespace_len);
+            }
+        }
+    };
+    Ok((format, Cursor::new(whitespace).chain(reader)))
+}
+
+pub(super) const REQUIRED_COLUMNS: [&str; 4] = ["type", "client", "tx", "amount"];
+
+/// Reads only the header row of CSV input and returns the column names, trimmed. The rest of `reader` is left unread.
+/// Empty input has no columns.
+pub fn validate_csv_header(reader: &mut impl BufRead) -> Result<Vec<String>, ProcessingError> {
+    let mut header = String::new();
+    reader.read_line(&mut header)?;
+
+    let mut header_reader = ReaderBuilder::new()
+        .has_headers(false)
+        .trim(Trim::All)
+        .from_reader(header.as_bytes());
+    Ok(match header_reader.records().next() {
+        Some(record) => record?.iter().map(str::to_owned).collect(),
+        None => Vec::new(),
+    })
+}
+
+/// Checks that every column the transaction records need is present. Fails with the first missing one.
+pub fn assert_required_columns(headers: &[String]) -> Result<(), ProcessingError> {
+    match REQUIRED_COLUMNS
+        .iter()
+        .find(|&&column| !headers.iter().any(|header| header == column))
+    {
+        Some(column) => Err(ProcessingError::MissingColumn(column.to_string())),
+        None => Ok(()),
+    }
+}
+
+/// Counts the newlines read through it. This is only accurate when the consumer doesn't read ahead of what it has
+/// parsed, as with `serde_json`, which reads byte by byte; the `csv` reader buffers its input instead.
+pub struct LineTrackingReader<R: Read> {
+    inner: R,
+    newlines_read: Rc<Cell<u64>>,
+}
+
+impl<R: Read> LineTrackingReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            newlines_read: Rc::default(),
+        }
+    }
+
+    /// The 1-based line of the last byte read.
+    pub fn current_line(&self) -> u64 {
+        self.newlines_read.get() + 1
+    }
+}
+
+impl<R: Read> Read for LineTrackingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        let newlines = buf[..len].iter().filter(|&&byte| byte == b'\n').count() as u64;
+        self.newlines_read.set(self.newlines_read.get() + newlines);
+        Ok(len)
+    }
+}
+
+/// Lazily parses JSON values and flattens top-level arrays. Each value comes with the line it ends on, except for
+/// array elements, whose lines are unknown. Parsing stops at the first syntax error, since there is no reliable way
+/// to resynchronize a JSON stream.
+pub(super) fn read_json_values(
+    json_stream: impl Read,
+) -> impl Iterator<Item = (Value, Option<u64>)> {
+    let json_stream = LineTrackingReader::new(json_stream);
+    let newlines_read = json_stream.newlines_read.clone();
+
+    serde_json::Deserializer::from_reader(json_stream)
+        .into_iter::<Value>()
+        .map_while(|result| result.map_err(|e| eprintln!("deserialize error: {e}")).ok())
+        .flat_map(move |value| match value {
+            Value::Array(values) => values.into_iter().map(|value| (value, None)).collect(),
+            value => vec![(value, Some(newlines_read.get() + 1))],
+        })
+}
+
+/// Parses a single line of CSV input with the columns in `headers`. Blank lines give `None`, and so do malformed ones,
+/// which are logged to stderr as when reading a whole stream.
+pub(super) fn parse_csv_line(line: &[u8], headers: &StringRecord) -> Option<Transaction> {
+    let mut reader = ReaderBuilder::new()
+        .has_headers(false)
+        .trim(Trim::All)
+        .from_reader(line);
+    reader
+        .records()
+        .next()?
+        .and_then(|record| record.deserialize::<InputCsvRecord>(Some(headers)))
+        .map_err(|e| eprintln!("deserialize error: {e}"))
+        .ok()
+        .and_then(Option::<Transaction>::from)
+}
+
+/// Accepts the CSV column names as keys, in snake_case or camelCase, e.g. `recordType` for `type`, and amounts as
+/// strings or as plain JSON numbers.
+impl TryFrom<Value> for InputCsvRecord {
+    type Error = serde_json::Error;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        let value = match value {
+            Value::Object(object) => Value::Object(
+                object
+                    .into_iter()
+                    .map(|(key, mut value)| {
+                        let key = match to_snake_case(&key).as_str() {
+                            "record_type" => "type".to_owned(),
+                            key => key.to_owned(),
+                        };
+                        // Amounts are parsed from strings to keep their exact precision.
+                        if let ("amount", Value::Number(number)) = (key.as_str(), &value) {
+                            value = Value::String(number.to_string());
+                        }
+                        (key, value)
+                    })
+                    .collect(),
+            ),
+            value => value,
+        };
+        serde_json::from_value(value)
+    }
+}
+
+fn to_snake_case(key: &str) -> String {
+    let mut snake_case = String::with_capacity(key.len());
+    for c in key.chars() {
+        if c.is_ascii_uppercase() {
+            snake_case.push('_');
+        }
+        snake_case.push(c.to_ascii_lowercase());
+    }
+    snake_case
+}
+
+#[derive(Serialize)]
+struct EncodedTransactionRecord {
+    #[serde(rename = "type")]
+    record_type: &'static str,
+    client: ClientId,
+    tx: TransactionId,
+    #[serde(with = "rust_decimal::serde::str_option")]
+    amount: Option<AmountType>,
+    /// Only written for corrections.
+    force: Option<bool>,
+}
+
+/// Writes transactions back as CSV input, e.g. to save a log for replay. The columns are `type`, `client`, `tx`,
+/// `amount`, which is empty for disputes, resolves and chargebacks, and `force`, which is only filled in for
+/// corrections.
+pub struct CsvTransactionEncoder<W: Write>(Writer<W>);
+
+impl<W: Write> CsvTransactionEncoder<W> {
+    pub fn new(inner: W) -> Self {
+        Self(Writer::from_writer(inner))
+    }
+
+    pub fn encode(&mut self, tx: &Transaction) -> Result<(), ProcessingError> {
+        self.0.serialize(EncodedTransactionRecord {
+            record_type: tx.tx_type.name(),
+            client: tx.client,
+            tx: tx.tx,
+            amount: tx.tx_type.amount(),
+            force: match tx.tx_type {
+                Correction(_, force) => Some(force),
+                _ => None,
+            },
+        })?;
+        Ok(())
+    }
+
+    /// Flushes the CSV and returns the underlying writer.
+    pub fn finish(self) -> Result<W, ProcessingError> {
+        Ok(self.0.into_inner().map_err(|e| e.into_error())?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Transaction, TransactionType};
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_input_record_from_json() {
+        let transaction =
+            |value| Transaction::try_from(InputCsvRecord::try_from(value).unwrap()).unwrap();
+        let expected = Transaction {
+            client: 1.into(),
+            tx: 2.into(),
+            tx_type: TransactionType::Deposit("1.5".parse().unwrap()),
+        };
+
+        assert_eq!(
+            transaction(json!({"type": "deposit", "client": 1, "tx": 2, "amount": "1.5"})),
+            expected
+        );
+        assert_eq!(
+            transaction(json!({"recordType": "deposit", "client": 1, "tx": 2, "amount": 1.5})),
+            expected
+        );
+        assert_eq!(
+            transaction(json!({"record_type": "dispute", "client": 1, "tx": 2})).tx_type,
+            TransactionType::Dispute
+        );
+        assert!(InputCsvRecord::try_from(json!({"type": "deposit", "client": 1})).is_err());
+    }
+
+    #[test]
+    fn test_csv_header() {
+        let mut reader = "type, client,tx,amount\ndeposit,1,1,1\n".as_bytes();
+        let headers = validate_csv_header(&mut reader).unwrap();
+        assert_eq!(headers, ["type", "client", "tx", "amount"]);
+        assert!(assert_required_columns(&headers).is_ok());
+        assert_eq!(reader, b"deposit,1,1,1\n");
+
+        let headers = validate_csv_header(&mut "type,client,amount,force".as_bytes()).unwrap();
+        assert!(matches!(
+            assert_required_columns(&headers),
+            Err(ProcessingError::MissingColumn(column)) if column == "tx"
+        ));
+        assert!(validate_csv_header(&mut "".as_bytes()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_auto_detect_format() {
+        let detect = |input: &str| {
+            let (format, mut reader) =
+                auto_detect_format(BufReader::with_capacity(2, input.as_bytes())).unwrap();
+            let mut rest = String::new();
+            reader.read_to_string(&mut rest).unwrap();
+            (format, rest)
+        };
+
+        assert_eq!(
+            detect("type,client\n"),
+            (InputFormat::Csv, "type,client\n".to_owned())
+        );
+        assert_eq!(
+            detect("  \n  {\"tx\":1}"),
+            (InputFormat::Json, "  \n  {\"tx\":1}".to_owned())
+        );
+        assert_eq!(detect("[]"), (InputFormat::Json, "[]".to_owned()));
+        assert_eq!(detect(""), (InputFormat::Csv, String::new()));
+    }
+
+    #[test]
+    fn test_csv_transaction_encoder_round_trip() {
+        let decode = |csv: &[u8]| -> Vec<Transaction> {
+            csv::Reader::from_reader(csv)
+                .deserialize::<InputCsvRecord>()
+                .map(|record| Transaction::try_from(record.unwrap()).unwrap())
+                .collect()
+        };
+        let transactions = decode(
+            b"type,client,tx,amount,force\n\
+              deposit,1,1,1.5,\n\
+              withdrawal,1,2,0.25,\n\
+              dispute,1,1,,\n\
+              resolve,1,1,,\n\
+              chargeback,1,1,,\n\
+              correction,1,3,-2,true\n",
+        );
+
+        let mut encoder = CsvTransactionEncoder::new(Vec::new());
+        for transaction in &transactions {
+            encoder.encode(transaction).unwrap();
+        }
+        let csv = encoder.finish().unwrap();
+        assert_eq!(
+            String::from_utf8(csv.clone()).unwrap(),
+            "type,client,tx,amount,force\n\
+             deposit,1,1,1.5,\n\
+             withdrawal,1,2,0.25,\n\
+             dispute,1,1,,\n\
+             resolve,1,1,,\n\
+             chargeback,1,1,,\n\
+             correction,1,3,-2,true\n"
+        );
+        assert_eq!(decode(&csv), transactions);
+    }
+}
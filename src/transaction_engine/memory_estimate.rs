@@ -0,0 +1,58 @@
+use super::{AmountType, ClientId, ClientState, DepositLog, Transaction, TransactionId};
+use std::collections::HashMap;
+use std::mem::size_of;
+
+/// Approximate heap usage of the engine state, in bytes.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryEstimate {
+    pub client_map_bytes: usize,
+    pub deposit_log_bytes: usize,
+    pub total_bytes: usize,
+}
+
+/// Estimates the memory held by the client states and the deposit log from their capacities, e.g. to size a machine
+/// for a transaction volume or to decide when to run `compact_deposit_log`. Each map slot costs its key and value plus
+/// one control byte; the transactions pending on locked clients are counted too. Allocator overhead is not.
+pub fn estimate_memory_usage(
+    clients: &HashMap<ClientId, ClientState>,
+    deposit_log: &DepositLog,
+) -> MemoryEstimate {
+    let pending_bytes: usize = clients
+        .values()
+        .map(|client_state| {
+            client_state.pending_locked_transactions.capacity() * size_of::<Transaction>()
+        })
+        .sum();
+    let client_map_bytes =
+        clients.capacity() * (size_of::<ClientId>() + size_of::<ClientState>() + 1) + pending_bytes;
+    let deposit_log_bytes = deposit_log.capacity()
+        * (size_of::<TransactionId>() + size_of::<(ClientId, AmountType, bool)>() + 1);
+    MemoryEstimate {
+        client_map_bytes,
+        deposit_log_bytes,
+        total_bytes: client_map_bytes + deposit_log_bytes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_memory_usage() {
+        assert_eq!(
+            estimate_memory_usage(&HashMap::new(), &DepositLog::new()),
+            MemoryEstimate::default()
+        );
+
+        let clients = HashMap::from([(1.into(), ClientState::default())]);
+        let deposit_log = DepositLog::from([(1.into(), (1.into(), 1.into(), false))]);
+        let estimate = estimate_memory_usage(&clients, &deposit_log);
+        assert!(clients.capacity() * size_of::<ClientState>() <= estimate.client_map_bytes);
+        assert!(deposit_log.capacity() * size_of::<TransactionId>() < estimate.deposit_log_bytes);
+        assert_eq!(
+            estimate.total_bytes,
+            estimate.client_map_bytes + estimate.deposit_log_bytes
+        );
+    }
+}
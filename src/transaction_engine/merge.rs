@@ -0,0 +1,150 @@
+use super::{
+    read_csv_transactions, without_source_lines, ProcessingError, ProcessingStats, Transaction,
+    TransactionId, TransactionType,
+};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+/// Which of the duplicate transactions across merged feeds survive. Deposits, withdrawals and corrections are
+/// duplicates when they share a transaction id. Disputes, resolves and chargebacks refer to the id of another
+/// transaction and may legitimately repeat, e.g. a dispute after a resolve, so they are never dropped.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DedupPolicy {
+    #[default]
+    KeepFirst,
+    /// Keeps the last occurrence, at the position of the first one.
+    KeepLast,
+    KeepAll,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct MergeConfig {
+    pub dedup_policy: DedupPolicy,
+}
+
+/// Same as `merge_csv_files_with_config` with the default config, which keeps the first occurrence of duplicates.
+pub fn merge_csv_files(
+    paths: &[impl AsRef<Path>],
+) -> Result<impl Iterator<Item = Transaction>, ProcessingError> {
+    merge_csv_files_with_config(paths, &MergeConfig::default())
+}
+
+/// Reads the CSV transactions of every file, in the order given, and returns them as one stream for a single
+/// processing run, with duplicates removed according to `config`. All files are read before this returns.
+pub fn merge_csv_files_with_config(
+    paths: &[impl AsRef<Path>],
+    config: &MergeConfig,
+) -> Result<impl Iterator<Item = Transaction>, ProcessingError> {
+    let mut transactions = Vec::new();
+    let mut positions = HashMap::new();
+    for path in paths {
+        let mut stats = ProcessingStats::default();
+        let file = BufReader::new(File::open(path)?);
+        for transaction in without_source_lines(read_csv_transactions(file, &mut stats)?) {
+            let key = match dedup_key(&transaction) {
+                Some(key) if config.dedup_policy != DedupPolicy::KeepAll => key,
+                _ => {
+                    transactions.push(transaction);
+                    continue;
+                }
+            };
+            match positions.get(&key) {
+                None => {
+                    positions.insert(key, transactions.len());
+                    transactions.push(transaction);
+                }
+                Some(&position) if config.dedup_policy == DedupPolicy::KeepLast => {
+                    transactions[position] = transaction
+                }
+                Some(_) => {}
+            }
+        }
+    }
+    Ok(transactions.into_iter())
+}
+
+/// The transaction id, for the transactions that introduce one. The ones that refer to another transaction have none.
+fn dedup_key(transaction: &Transaction) -> Option<TransactionId> {
+    match transaction.tx_type {
+        TransactionType::Deposit(_)
+        | TransactionType::Withdrawal(_)
+        | TransactionType::Correction(..) => Some(transaction.tx),
+        TransactionType::Dispute | TransactionType::Resolve | TransactionType::Chargeback => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::transaction_processing_logic::process_transactions_and_return_client_states;
+    use super::super::AmountType;
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_merge_csv_files() {
+        let dir =
+            std::env::temp_dir().join(format!("transaction_engine_merge_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let paths = [dir.join("a.csv"), dir.join("b.csv")];
+        fs::write(
+            &paths[0],
+            "type,client,tx,amount\ndeposit,1,1,1\ndeposit,1,2,2\n",
+        )
+        .unwrap();
+        fs::write(
+            &paths[1],
+            "type,client,tx,amount\ndeposit,1,2,3\ndispute,1,2,\ndeposit,2,3,4\n",
+        )
+        .unwrap();
+
+        let amounts = |dedup_policy| {
+            merge_csv_files_with_config(&paths, &MergeConfig { dedup_policy })
+                .unwrap()
+                .map(|transaction| match transaction.tx_type {
+                    TransactionType::Deposit(amount) => amount,
+                    _ => AmountType::ZERO,
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            merge_csv_files(&paths)
+                .unwrap()
+                .map(|transaction| transaction.tx)
+                .collect::<Vec<_>>(),
+            vec![1.into(), 2.into(), 2.into(), 3.into()]
+        );
+        assert_eq!(
+            amounts(DedupPolicy::KeepFirst),
+            [1, 2, 0, 4].map(AmountType::from)
+        );
+        assert_eq!(
+            amounts(DedupPolicy::KeepLast),
+            [1, 3, 0, 4].map(AmountType::from)
+        );
+        assert_eq!(
+            amounts(DedupPolicy::KeepAll),
+            [1, 2, 3, 0, 4].map(AmountType::from)
+        );
+
+        // Repeated disputes of the same deposit within a feed are all kept.
+        fs::write(
+            &paths[0],
+            "type,client,tx,amount\ndeposit,1,1,5\ndispute,1,1,\nresolve,1,1,\n\
+             dispute,1,1,\nchargeback,1,1,\n",
+        )
+        .unwrap();
+        let clients =
+            process_transactions_and_return_client_states(merge_csv_files(&paths[..1]).unwrap());
+        assert_eq!(clients[&1.into()].available, AmountType::ZERO);
+        assert!(clients[&1.into()].locked);
+
+        assert!(matches!(
+            merge_csv_files(&[dir.join("missing.csv")]),
+            Err(ProcessingError::Io(_))
+        ));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
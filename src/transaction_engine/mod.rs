@@ -1,12 +1,90 @@
-use csv::{ReaderBuilder, Trim, Writer};
+use csv::{ReaderBuilder, StringRecord, Trim, Writer, WriterBuilder};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::error::Error;
-use std::io::Read;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
 
-type ClientId = u16;
-type TransactionId = u32;
-type AmountType = Decimal;
+pub use account_ledger::{
+    backfill_transactions, AccountLedger, AccountLedgerCheckpoint, BatchResult,
+    ClientStatesCsvReader,
+};
+pub use amount_delta::AmountDelta;
+pub use chained_reader::ChainedReader;
+pub use change_event::ChangeEvent;
+pub use client_id::ClientId;
+pub use client_state_map::ClientStateMap;
+pub use client_state_reports::{
+    chargeback_rate, client_summary_line, clients_exceeding_chargeback_rate,
+    clients_under_dispute_count, diff_deposit_logs, explain_transaction, format_amount,
+    net_flow_report, partition_clients_by_status, partition_clients_by_status_ref,
+    summarize_by_lock_status, total_under_dispute, verify_no_negative_balances, zeroed_out_clients,
+    ClientFlowReport, DepositLogDiff, DisputeSummary, LockStatusSummary, LockedAccountInfo,
+    NegativeBalanceReport,
+};
+#[cfg(feature = "admin-operations")]
+pub use corrections::{apply_corrections, CorrectionResult};
+#[cfg(feature = "multi-currency")]
+pub use currency::apply_exchange_rate;
+#[cfg(feature = "signals")]
+pub use graceful_shutdown::install_shutdown_handler;
+pub use ignore_reason::IgnoreReason;
+pub use input_format::{
+    assert_required_columns, auto_detect_format, validate_csv_header, CsvTransactionEncoder,
+    InputFormat, LineTrackingReader, SniffedReader,
+};
+pub use memory_estimate::{estimate_memory_usage, MemoryEstimate};
+pub use merge::{merge_csv_files, merge_csv_files_with_config, DedupPolicy, MergeConfig};
+pub use multi_client_processor::MultiClientProcessor;
+pub use output_format::{
+    write_client_states, write_client_states_as_json_stream, write_client_states_with_config,
+    GzipCsvWriter, OutputFormat, WriterConfig, CSV_SCHEMA_VERSION,
+};
+pub use processing_config::{ProcessingConfig, StrictAmountValidator, UnknownTransactionPolicy};
+pub use processing_error::ProcessingError;
+pub use processing_mode::{process_interactive, process_streaming, ProcessingMode};
+pub use processing_report::{AuditEntry, ProcessingReport, TransactionTypeStats};
+pub use processing_warning::ProcessingWarning;
+#[cfg(feature = "protobuf")]
+pub use protobuf::{
+    decode_client_state_from_proto, encode_client_state_to_proto, encode_transaction_to_proto,
+};
+pub use retry_reader::RetryReader;
+pub use ring_buffer_transaction_source::RingBufferTransactionSource;
+pub use sql_output::process_transactions_and_emit_sql;
+#[cfg(feature = "sqlite")]
+pub use sqlite_output::process_transactions_to_sqlite;
+#[cfg(any(test, feature = "test-helpers"))]
+pub use test_helpers::{
+    assert_csv_output_matches, generate_sample_csv, random_transactions, AssertionError,
+};
+pub use transaction_filter::{
+    And, ByAmountRange, ByClientId, ByTransactionType, Not, Or, TransactionFilter,
+    TransactionTypeFilter,
+};
+pub use transaction_id::TransactionId;
+pub use transaction_log::{
+    reprocess_disputed_transactions, ApplicationResult, TransactionLog, TransactionLogWriter,
+};
+pub use transaction_processing_logic::{
+    compact_deposit_log, deduplicate_transactions, max_concurrent_holds, oldest_open_dispute,
+    process_transactions_and_return_change_events,
+    process_transactions_and_return_client_states_with_error_count,
+    process_transactions_and_return_client_states_with_log,
+    process_transactions_and_return_dispute_summary, process_transactions_and_return_flow_totals,
+    process_transactions_and_return_locked_accounts, process_transactions_and_return_top_n,
+    process_transactions_dry_run, process_transactions_filtered, process_transactions_in_batches,
+    process_transactions_into_report, process_transactions_with_config, replay_transactions_up_to,
+    simulate_transaction, sorted_transactions, transactions_touching_client, ClientState,
+    ClientStateDelta, DepositLog, FlowTotals, ReplayLimit, SimulationResult, Transaction,
+    TransactionType,
+};
+pub use transaction_sequence_validator::{Severity, TransactionSequenceValidator, ValidationIssue};
+
+pub type AmountType = Decimal;
 
 #[derive(Debug, Deserialize)]
 struct InputCsvRecord {
@@ -15,10 +93,15 @@ struct InputCsvRecord {
 
     client: ClientId,
     tx: TransactionId,
-    amount: Option<AmountType>,
+    /// Parsed when converting to a `Transaction`, rather than by serde, for a clearer error message when malformed.
+    amount: Option<String>,
+
+    /// Only meaningful for corrections; the column may be absent from the input altogether.
+    #[serde(default)]
+    force: Option<bool>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 struct OutputCsvRecord {
     client: ClientId,
 
@@ -32,37 +115,1010 @@ struct OutputCsvRecord {
     total: AmountType,
 
     locked: bool,
+
+    /// Only written with `WriterConfig::extended`.
+    #[cfg(feature = "detailed-stats")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    deposit_count: Option<u32>,
+    #[cfg(feature = "detailed-stats")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    withdrawal_count: Option<u32>,
+    #[cfg(feature = "detailed-stats")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    dispute_count: Option<u32>,
+    #[cfg(feature = "detailed-stats")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    resolve_count: Option<u32>,
+    #[cfg(feature = "detailed-stats")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    chargeback_count: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+struct WarningCsvRecord {
+    line_number: Option<u64>,
+    tx_id: TransactionId,
+    client_id: ClientId,
+    warning_type: &'static str,
+    message: String,
+}
+
+impl From<&ProcessingWarning> for WarningCsvRecord {
+    fn from(warning: &ProcessingWarning) -> Self {
+        Self {
+            line_number: warning.source_line(),
+            tx_id: warning.tx(),
+            client_id: warning.client(),
+            warning_type: warning.kind(),
+            message: warning.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ProcessingStats {
+    /// Input records successfully converted into transactions.
+    pub transactions_read: u64,
+    /// Input records that failed deserialization or conversion.
+    pub records_rejected: u64,
+    /// The part of `records_rejected` with an unrecognized type.
+    pub records_of_unknown_type: u64,
+    /// Transactions that were processed but ignored, e.g. for lack of funds, see `IgnoreReason`.
+    pub transactions_ignored: u64,
+    /// Problems found while reading the input rather than processing it, i.e. `PrecisionLoss`. Filled in by the
+    /// functions that read a stream and return these stats, e.g. `process_transactions_and_write_client_states` and
+    /// `process_streaming`; `process_stream_with_config` moves them into `ProcessingReport::warnings` instead.
+    pub read_warnings: Vec<ProcessingWarning>,
+    pub clients: usize,
+    /// See `total_under_dispute`.
+    pub total_under_dispute: AmountType,
+    /// See `clients_under_dispute_count`.
+    pub clients_under_dispute: usize,
+}
+
+impl ProcessingStats {
+    /// Fills in the figures about the final client states.
+    fn record_clients(&mut self, clients: &HashMap<ClientId, ClientState>) {
+        self.clients = clients.len();
+        self.total_under_dispute = total_under_dispute(clients);
+        self.clients_under_dispute = clients_under_dispute_count(clients);
+    }
+}
+
+/// Lazily turns a CSV stream into transactions, each with the 1-based line it starts on. The header is checked up
+/// front; malformed records are logged to stderr and counted in `stats`.
+fn read_csv_transactions<'a>(
+    csv_transaction_stream: impl BufRead + 'a,
+    stats: &'a mut ProcessingStats,
+) -> Result<impl Iterator<Item = (Transaction, Option<u64>)> + 'a, ProcessingError> {
+    read_csv_transactions_with_strict_amounts(csv_transaction_stream, None, stats)
 }
 
-/// Both a File and a TcpStream can be accepted.
+/// Same as `read_csv_transactions`, also rejecting the records whose amounts `strict_amounts` doesn't accept as
+/// written, i.e. before they are rounded.
+fn read_csv_transactions_with_strict_amounts<'a>(
+    mut csv_transaction_stream: impl BufRead + 'a,
+    strict_amounts: Option<StrictAmountValidator>,
+    stats: &'a mut ProcessingStats,
+) -> Result<impl Iterator<Item = (Transaction, Option<u64>)> + 'a, ProcessingError> {
+    // Leading blank lines are skipped before the header, as by the `csv` reader.
+    let mut header_line = 1;
+    let headers = loop {
+        let headers = validate_csv_header(&mut csv_transaction_stream)?;
+        if !headers.is_empty() || csv_transaction_stream.fill_buf()?.is_empty() {
+            break headers;
+        }
+        header_line += 1;
+    };
+    // Empty input is fine, it just has no transactions.
+    if !headers.is_empty() {
+        assert_required_columns(&headers)?;
+    }
+    let headers = StringRecord::from(headers);
+
+    let reader = ReaderBuilder::new()
+        .has_headers(false)
+        .trim(Trim::All)
+        .from_reader(csv_transaction_stream);
+    Ok(convert_records(
+        reader.into_records().map(move |result| {
+            // The header line was read before the reader started counting.
+            let line = result
+                .as_ref()
+                .ok()
+                .and_then(|record| record.position())
+                .map(|position| position.line() + header_line);
+            let record =
+                result.and_then(|record| record.deserialize::<InputCsvRecord>(Some(&headers)));
+            (record, line)
+        }),
+        strict_amounts,
+        stats,
+    ))
+}
+
+/// Transactions along with the line they were read from, if known.
+type SourcedTransactions<'a> = Box<dyn Iterator<Item = (Transaction, Option<u64>)> + 'a>;
+
+/// Same as `read_csv_transactions`, with the stream in `format`.
+fn read_transactions<'a>(
+    transaction_stream: impl Read + 'a,
+    format: InputFormat,
+    stats: &'a mut ProcessingStats,
+) -> Result<SourcedTransactions<'a>, ProcessingError> {
+    read_transactions_with_strict_amounts(transaction_stream, format, None, stats)
+}
+
+/// Same as `read_csv_transactions_with_strict_amounts`, with the stream in `format`.
+fn read_transactions_with_strict_amounts<'a>(
+    transaction_stream: impl Read + 'a,
+    format: InputFormat,
+    strict_amounts: Option<StrictAmountValidator>,
+    stats: &'a mut ProcessingStats,
+) -> Result<SourcedTransactions<'a>, ProcessingError> {
+    let transaction_stream = BufReader::new(transaction_stream);
+    match format {
+        InputFormat::Auto => {
+            let (format, transaction_stream) = auto_detect_format(transaction_stream)?;
+            read_transactions_in_format(transaction_stream, format, strict_amounts, stats)
+        }
+        format => read_transactions_in_format(transaction_stream, format, strict_amounts, stats),
+    }
+}
+
+fn read_transactions_in_format<'a>(
+    transaction_stream: impl BufRead + 'a,
+    format: InputFormat,
+    strict_amounts: Option<StrictAmountValidator>,
+    stats: &'a mut ProcessingStats,
+) -> Result<SourcedTransactions<'a>, ProcessingError> {
+    Ok(match format {
+        InputFormat::Json => Box::new(convert_records(
+            input_format::read_json_values(transaction_stream)
+                .map(|(value, line)| (InputCsvRecord::try_from(value), line)),
+            strict_amounts,
+            stats,
+        )),
+        _ => Box::new(read_csv_transactions_with_strict_amounts(
+            transaction_stream,
+            strict_amounts,
+            stats,
+        )?),
+    })
+}
+
+fn convert_records<'a>(
+    records: impl Iterator<Item = (Result<InputCsvRecord, impl Display>, Option<u64>)> + 'a,
+    strict_amounts: Option<StrictAmountValidator>,
+    stats: &'a mut ProcessingStats,
+) -> impl Iterator<Item = (Transaction, Option<u64>)> + 'a {
+    records.filter_map(move |(result, line)| {
+        let transaction = result
+            .map_err(|e| eprintln!("deserialize error: {e}"))
+            .ok()
+            .and_then(|record: InputCsvRecord| {
+                if record.record_type.parse::<TransactionTypeFilter>().is_err() {
+                    stats.records_of_unknown_type += 1;
+                }
+                let amount = record.amount.clone();
+                let transaction: Option<Transaction> = record.into();
+                // Checked as written, since rounding changes the scale.
+                let transaction = transaction.filter(|transaction| {
+                    match (
+                        strict_amounts,
+                        transaction_processing_logic::written_amount(
+                            amount.as_deref(),
+                            transaction,
+                        ),
+                    ) {
+                        (Some(validator), Some(written)) if !validator.accepts(written) => {
+                            transaction_processing_logic::log_ignored(
+                                transaction,
+                                &IgnoreReason::AmountScaleMismatch,
+                            );
+                            false
+                        }
+                        _ => true,
+                    }
+                });
+                if let Some(transaction) = &transaction {
+                    if let Some((original, rounded)) =
+                        transaction_processing_logic::precision_loss(amount.as_deref(), transaction)
+                    {
+                        stats.read_warnings.push(ProcessingWarning::PrecisionLoss {
+                            client: transaction.client,
+                            tx: transaction.tx,
+                            original,
+                            rounded,
+                            source_line: line,
+                        });
+                    }
+                }
+                transaction
+            });
+        match transaction {
+            Some(_) => stats.transactions_read += 1,
+            None => stats.records_rejected += 1,
+        }
+        transaction.map(|transaction| (transaction, line))
+    })
+}
+
+fn without_source_lines<'a>(
+    transactions: impl Iterator<Item = (Transaction, Option<u64>)> + 'a,
+) -> impl Iterator<Item = Transaction> + 'a {
+    transactions.map(|(transaction, _)| transaction)
+}
+
+/// The number of CSV rows that parse as transactions, without processing them, e.g. as the total of a progress bar.
+/// Malformed rows are logged to stderr and not counted, as when processing.
+pub fn count_csv_transactions(reader: impl Read) -> Result<u64, ProcessingError> {
+    let mut stats = ProcessingStats::default();
+    read_csv_transactions(BufReader::new(reader), &mut stats)?.for_each(drop);
+    Ok(stats.transactions_read)
+}
+
+/// A quick estimate of `count_csv_transactions`: the number of lines after the header, without parsing them. Blank
+/// and malformed lines are counted too, and a read error ends the count.
+pub fn count_csv_transactions_fast(reader: impl Read) -> u64 {
+    let mut reader = BufReader::new(reader);
+    let mut newlines = 0;
+    let mut last_byte = None;
+    while let Ok(buffer) = reader.fill_buf() {
+        if buffer.is_empty() {
+            break;
+        }
+        newlines += buffer.iter().filter(|&&byte| byte == b'\n').count() as u64;
+        last_byte = buffer.last().copied();
+        let len = buffer.len();
+        reader.consume(len);
+    }
+    // The last line counts even without a newline at its end.
+    let lines = match last_byte {
+        Some(b'\n') | None => newlines,
+        Some(_) => newlines + 1,
+    };
+    lines.saturating_sub(1)
+}
+
+/// Both a File and a TcpStream can be accepted. Despite the name, the input can also be JSON, see `InputFormat`.
 pub fn process_csv_transactions_and_return_csv_client_states(
     csv_transaction_stream: impl Read,
+    input_format: InputFormat,
 ) -> Result<String, Box<dyn Error>> {
-    let iter_transactions = ReaderBuilder::new()
-        .trim(Trim::All)
-        .from_reader(csv_transaction_stream)
-        .into_deserialize::<InputCsvRecord>()
-        .filter_map(|result| result.map_err(|e| eprintln!("deserialize error: {e}")).ok())
-        .filter_map(|record| {
-            record
-                .try_into()
-                .map_err(|e| eprintln!("conversion (InputCsvRecord -> Transaction) error: {e}"))
-                .ok()
-        });
+    let mut stats = ProcessingStats::default();
+    let clients = transaction_processing_logic::process_transactions_and_return_client_states(
+        without_source_lines(read_transactions(
+            csv_transaction_stream,
+            input_format,
+            &mut stats,
+        )?),
+    );
+
+    let mut csv_output = Vec::new();
+    write_client_states(clients, &mut csv_output, OutputFormat::Csv)?;
+    Ok(String::from_utf8(csv_output)?)
+}
 
+/// Same as `process_csv_transactions_and_return_csv_client_states`, but ends the CSV with a `TOTAL` row, see
+/// `WriterConfig::include_totals_row`.
+pub fn process_csv_transactions_and_return_csv_with_running_totals(
+    csv_transaction_stream: impl Read,
+    input_format: InputFormat,
+) -> Result<String, ProcessingError> {
+    let mut stats = ProcessingStats::default();
     let clients = transaction_processing_logic::process_transactions_and_return_client_states(
-        iter_transactions,
+        without_source_lines(read_transactions(
+            csv_transaction_stream,
+            input_format,
+            &mut stats,
+        )?),
     );
 
-    let csv_output = {
-        let mut writer = Writer::from_writer(Vec::new());
-        for output_record in clients.into_iter().map(Into::<OutputCsvRecord>::into) {
-            writer.serialize(output_record)?;
-        }
-        String::from_utf8(writer.into_inner()?)?
+    let mut csv_output = Vec::new();
+    write_client_states_with_config(
+        clients,
+        &mut csv_output,
+        OutputFormat::Csv,
+        &WriterConfig {
+            include_totals_row: true,
+            ..Default::default()
+        },
+    )?;
+    Ok(String::from_utf8(csv_output).expect("the CSV is written as UTF-8"))
+}
+
+/// Streaming counterpart of `process_csv_transactions_and_return_csv_client_states`, writing the client states to
+/// `output` in `output_format` instead of returning them.
+pub fn process_transactions_and_write_client_states(
+    input: impl Read,
+    input_format: InputFormat,
+    output: impl Write,
+    output_format: OutputFormat,
+) -> Result<ProcessingStats, ProcessingError> {
+    let mut stats = ProcessingStats::default();
+    let clients = transaction_processing_logic::process_transactions_and_return_client_states(
+        without_source_lines(read_transactions(input, input_format, &mut stats)?),
+    );
+    stats.record_clients(&clients);
+
+    write_client_states(clients, output, output_format)?;
+    Ok(stats)
+}
+
+/// Processes a stream with `config` into a full report. Warnings carry the line of the input they originate from.
+/// Failed reads are retried as configured by `config.max_io_retries`.
+pub fn process_stream_with_config(
+    input: impl Read,
+    input_format: InputFormat,
+    config: &ProcessingConfig,
+) -> Result<ProcessingReport, ProcessingError> {
+    let input = RetryReader::new(input, config.max_io_retries, config.retry_delay);
+    let mut stats = ProcessingStats::default();
+    let transactions = read_transactions_with_strict_amounts(
+        input,
+        input_format,
+        config.strict_amounts,
+        &mut stats,
+    )?;
+    // The amounts were checked as they were read, before rounding.
+    let config = ProcessingConfig {
+        strict_amounts: None,
+        ..config.clone()
     };
+    let mut report = transaction_processing_logic::process_transactions(transactions, &config)?;
+    report.stats.records_rejected = stats.records_rejected;
+    report.stats.records_of_unknown_type = stats.records_of_unknown_type;
+    report.transaction_type_stats.unknown = stats.records_of_unknown_type;
+    // Each transaction is read before it is processed.
+    report.warnings.splice(0..0, stats.read_warnings);
+    Ok(report)
+}
+
+/// Processes CSV transactions and returns the changes they made to the accounts, in order, see `ChangeEvent`.
+pub fn process_csv_and_return_change_events(
+    reader: impl Read,
+) -> Result<Vec<ChangeEvent>, ProcessingError> {
+    let mut stats = ProcessingStats::default();
+    let events = transaction_processing_logic::process_transactions_and_return_change_events(
+        without_source_lines(read_csv_transactions(BufReader::new(reader), &mut stats)?),
+    );
+    Ok(events)
+}
+
+/// Processes CSV transactions and returns only the clients locked by a chargeback, see `LockedAccountInfo`.
+pub fn process_csv_and_return_locked_accounts(
+    reader: impl Read,
+) -> Result<Vec<LockedAccountInfo>, ProcessingError> {
+    let mut stats = ProcessingStats::default();
+    let locked_accounts =
+        transaction_processing_logic::process_transactions_and_return_locked_accounts(
+            without_source_lines(read_csv_transactions(BufReader::new(reader), &mut stats)?),
+        );
+    Ok(locked_accounts)
+}
+
+/// Processes CSV transactions and returns the peak of the total held, with the transaction that reached it, see
+/// `max_concurrent_holds`.
+pub fn process_csv_and_return_max_concurrent_holds(
+    reader: impl Read,
+) -> Result<(AmountType, Option<Transaction>), ProcessingError> {
+    let mut stats = ProcessingStats::default();
+    let peak = transaction_processing_logic::max_concurrent_holds(without_source_lines(
+        read_csv_transactions(BufReader::new(reader), &mut stats)?,
+    ));
+    Ok(peak)
+}
+
+/// Processes CSV transactions and returns the clients with disputed funds still outstanding, see `DisputeSummary`.
+pub fn process_csv_and_return_dispute_summary(
+    reader: impl Read,
+) -> Result<Vec<DisputeSummary>, ProcessingError> {
+    let mut stats = ProcessingStats::default();
+    let summaries = transaction_processing_logic::process_transactions_and_return_dispute_summary(
+        without_source_lines(read_csv_transactions(BufReader::new(reader), &mut stats)?),
+    );
+    Ok(summaries)
+}
+
+/// Processes CSV transactions and returns the clients left with a zero balance, as `(locked, unlocked)`, see
+/// `zeroed_out_clients`.
+pub fn process_csv_and_return_zeroed_out_clients(
+    reader: impl Read,
+) -> Result<(Vec<ClientId>, Vec<ClientId>), ProcessingError> {
+    let mut stats = ProcessingStats::default();
+    let clients = transaction_processing_logic::process_transactions_and_return_client_states(
+        without_source_lines(read_csv_transactions(BufReader::new(reader), &mut stats)?),
+    );
+    Ok(zeroed_out_clients(&clients))
+}
+
+/// Same as `process_csv_transactions_and_return_csv_client_states` for CSV input, but with the clients sorted by total
+/// balance, largest first, and by client id among equal totals.
+pub fn process_csv_and_sort_by_balance_descending(
+    reader: impl Read,
+) -> Result<String, ProcessingError> {
+    let mut stats = ProcessingStats::default();
+    let clients = transaction_processing_logic::process_transactions_and_return_client_states(
+        without_source_lines(read_csv_transactions(BufReader::new(reader), &mut stats)?),
+    );
+    let mut clients: Vec<_> = clients.into_iter().collect();
+    clients.sort_unstable_by_key(|(client_id, client_state)| {
+        (
+            std::cmp::Reverse(client_state.available + client_state.held),
+            *client_id,
+        )
+    });
+
+    let mut csv_output = Vec::new();
+    write_client_states(clients, &mut csv_output, OutputFormat::Csv)?;
+    Ok(String::from_utf8(csv_output).expect("client states are written as UTF-8"))
+}
+
+/// Processes CSV transactions, writing the client states as CSV to `states_output` and the warnings as CSV to
+/// `warnings_output`, with the columns `line_number`, `tx_id`, `client_id`, `warning_type` and `message`.
+pub fn process_csv_and_write_two_outputs(
+    input: impl Read,
+    states_output: impl Write,
+    warnings_output: impl Write,
+) -> Result<ProcessingStats, ProcessingError> {
+    let report = process_stream_with_config(input, InputFormat::Csv, &ProcessingConfig::default())?;
+
+    // The header is written up front, since serialize only writes it along with the first record.
+    let mut writer = WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(warnings_output);
+    writer.write_record([
+        "line_number",
+        "tx_id",
+        "client_id",
+        "warning_type",
+        "message",
+    ])?;
+    for warning in &report.warnings {
+        writer.serialize(WarningCsvRecord::from(warning))?;
+    }
+    writer.flush()?;
+
+    write_client_states(
+        report.client_states.into_sorted_vec(),
+        states_output,
+        OutputFormat::Csv,
+    )?;
+    Ok(report.stats)
+}
+
+/// The common "file in, file out" case: reads CSV transactions from `input_path` and writes the client states as
+/// JSON to `output_path`.
+pub fn process_csv_to_json_file(
+    input_path: &Path,
+    output_path: &Path,
+) -> Result<ProcessingStats, ProcessingError> {
+    process_transactions_and_write_client_states(
+        File::open(input_path)?,
+        InputFormat::Csv,
+        BufWriter::new(File::create(output_path)?),
+        OutputFormat::Json,
+    )
+}
+
+const CLIENT_ID_PLACEHOLDER: &str = "{client_id}";
+
+/// Processes the whole stream first, then writes each client's state to its own CSV file in `output_dir`. The file
+/// name is `filename_pattern` with `{client_id}` replaced, e.g. `"client_{client_id}.csv"`.
+pub fn process_csv_and_split_output_by_client(
+    input: impl Read,
+    output_dir: &Path,
+    filename_pattern: &str,
+) -> Result<ProcessingStats, ProcessingError> {
+    if !filename_pattern.contains(CLIENT_ID_PLACEHOLDER) {
+        return Err(ProcessingError::InvalidFilenamePattern(
+            filename_pattern.to_owned(),
+        ));
+    }
+
+    let mut stats = ProcessingStats::default();
+    let clients = transaction_processing_logic::process_transactions_and_return_client_states(
+        without_source_lines(read_csv_transactions(BufReader::new(input), &mut stats)?),
+    );
+    stats.record_clients(&clients);
+
+    for (client_id, client_state) in clients {
+        let file_name = filename_pattern.replace(CLIENT_ID_PLACEHOLDER, &client_id.to_string());
+        let mut writer = Writer::from_path(output_dir.join(file_name))?;
+        writer.serialize(OutputCsvRecord::from((client_id, client_state)))?;
+        writer.flush()?;
+    }
+
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_split_output_by_client() {
+        let output_dir =
+            std::env::temp_dir().join(format!("transaction_engine_split_{}", std::process::id()));
+        fs::create_dir_all(&output_dir).unwrap();
+
+        let input = "type,client,tx,amount\n\
+                     deposit,1,1,1.5\n\
+                     deposit,2,2,2\n\
+                     withdrawal,2,3,abc\n";
+        let stats = process_csv_and_split_output_by_client(
+            input.as_bytes(),
+            &output_dir,
+            "client_{client_id}.csv",
+        )
+        .unwrap();
+
+        assert_eq!(
+            stats,
+            ProcessingStats {
+                transactions_read: 2,
+                records_rejected: 1,
+                records_of_unknown_type: 0,
+                clients: 2,
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            fs::read_to_string(output_dir.join("client_1.csv")).unwrap(),
+            "client,available,held,total,locked\n1,1.5,0,1.5,false\n"
+        );
+        assert_eq!(
+            fs::read_to_string(output_dir.join("client_2.csv")).unwrap(),
+            "client,available,held,total,locked\n2,2,0,2,false\n"
+        );
+        assert!(matches!(
+            process_csv_and_split_output_by_client(input.as_bytes(), &output_dir, "clients.csv"),
+            Err(ProcessingError::InvalidFilenamePattern(_))
+        ));
+
+        fs::remove_dir_all(&output_dir).unwrap();
+    }
+
+    #[test]
+    fn test_change_events() {
+        let amount = |amount| AmountType::from_str_exact(amount).unwrap();
+        let events = process_csv_and_return_change_events(
+            "type,client,tx,amount\n\
+             deposit,1,1,2\n\
+             withdrawal,1,2,5\n\
+             dispute,1,1,\n\
+             chargeback,1,1,\n"
+                .as_bytes(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            events,
+            vec![
+                ChangeEvent::BalanceChanged {
+                    client: 1.into(),
+                    delta_available: amount("2").into(),
+                    delta_held: amount("0").into(),
+                    new_available: amount("2"),
+                    new_held: amount("0"),
+                },
+                ChangeEvent::BalanceChanged {
+                    client: 1.into(),
+                    delta_available: amount("-2").into(),
+                    delta_held: amount("2").into(),
+                    new_available: amount("0"),
+                    new_held: amount("2"),
+                },
+                ChangeEvent::BalanceChanged {
+                    client: 1.into(),
+                    delta_available: amount("0").into(),
+                    delta_held: amount("-2").into(),
+                    new_available: amount("0"),
+                    new_held: amount("0"),
+                },
+                ChangeEvent::ClientLocked { client: 1.into() },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sort_by_balance_descending() {
+        let input = "type,client,tx,amount\n\
+                     deposit,1,1,2\n\
+                     deposit,2,2,10\n\
+                     deposit,3,3,1\n\
+                     deposit,4,4,2\n\
+                     dispute,2,2,\n";
+
+        assert_eq!(
+            process_csv_and_sort_by_balance_descending(input.as_bytes()).unwrap(),
+            "client,available,held,total,locked\n\
+             2,0,10,10,false\n\
+             1,2,0,2,false\n\
+             4,2,0,2,false\n\
+             3,1,0,1,false\n"
+        );
+    }
+
+    #[test]
+    fn test_locked_accounts() {
+        let locked_accounts = process_csv_and_return_locked_accounts(
+            "type,client,tx,amount\n\
+             deposit,1,1,2\n\
+             deposit,2,2,3\n\
+             deposit,2,3,4\n\
+             dispute,2,2,\n\
+             chargeback,2,2,\n\
+             dispute,2,3,\n\
+             chargeback,2,3,\n"
+                .as_bytes(),
+        )
+        .unwrap();
+
+        assert_eq!(locked_accounts.len(), 1);
+        assert_eq!(locked_accounts[0].client_id, 2.into());
+        assert_eq!(locked_accounts[0].chargeback_tx_id, 2.into());
+        assert_eq!(
+            locked_accounts[0].chargeback_amount,
+            AmountType::from_str_exact("3").unwrap()
+        );
+        assert!(locked_accounts[0].client_state.locked);
+        assert_eq!(
+            locked_accounts[0]
+                .client_state
+                .pending_locked_transactions
+                .len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_zeroed_out_clients() {
+        let zeroed_out_clients = process_csv_and_return_zeroed_out_clients(
+            "type,client,tx,amount\n\
+             deposit,1,1,2\n\
+             dispute,1,1,\n\
+             chargeback,1,1,\n\
+             deposit,2,2,3\n\
+             withdrawal,2,3,3\n\
+             deposit,3,4,1\n"
+                .as_bytes(),
+        )
+        .unwrap();
+
+        assert_eq!(zeroed_out_clients, (vec![1.into()], vec![2.into()]));
+    }
+
+    #[test]
+    fn test_max_concurrent_holds() {
+        let (peak, transaction) = process_csv_and_return_max_concurrent_holds(
+            "type,client,tx,amount\n\
+             deposit,1,1,2\n\
+             deposit,2,2,3\n\
+             dispute,1,1,\n\
+             dispute,2,2,\n\
+             resolve,1,1,\n\
+             dispute,1,1,\n\
+             chargeback,2,2,\n"
+                .as_bytes(),
+        )
+        .unwrap();
+
+        assert_eq!(peak, 5.into());
+        assert_eq!(
+            transaction,
+            Some(Transaction {
+                client: 2.into(),
+                tx: 2.into(),
+                tx_type: TransactionType::Dispute,
+            })
+        );
+    }
+
+    #[test]
+    fn test_dispute_summary() {
+        let summaries = process_csv_and_return_dispute_summary(
+            "type,client,tx,amount\n\
+             deposit,1,1,2\n\
+             deposit,1,2,3\n\
+             deposit,2,3,4\n\
+             dispute,1,2,\n\
+             dispute,1,1,\n\
+             dispute,2,3,\n\
+             resolve,2,3,\n"
+                .as_bytes(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            summaries,
+            [DisputeSummary {
+                client: 1.into(),
+                open_disputes: vec![1.into(), 2.into()],
+                total_held: 5.into(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_two_outputs() {
+        let mut states_output = Vec::new();
+        let mut warnings_output = Vec::new();
+        let stats = process_csv_and_write_two_outputs(
+            "type,client,tx,amount\n\
+             deposit,1,1,2\n\
+             dispute,1,7,\n"
+                .as_bytes(),
+            &mut states_output,
+            &mut warnings_output,
+        )
+        .unwrap();
+
+        assert_eq!(stats.transactions_read, 2);
+        assert_eq!(
+            String::from_utf8(states_output).unwrap(),
+            "client,available,held,total,locked\n1,2,0,2,false\n"
+        );
+        assert_eq!(
+            String::from_utf8(warnings_output).unwrap(),
+            "line_number,tx_id,client_id,warning_type,message\n\
+             3,7,1,unknown_transaction,client 1 referenced unknown transaction 7\n"
+        );
+    }
+
+    #[test]
+    fn test_csv_to_json_file() {
+        let output_dir =
+            std::env::temp_dir().join(format!("transaction_engine_json_{}", std::process::id()));
+        fs::create_dir_all(&output_dir).unwrap();
+        let input_path = output_dir.join("input.csv");
+        let output_path = output_dir.join("output.json");
+        fs::write(&input_path, "type,client,tx,amount\ndeposit,1,1,1.5\n").unwrap();
+
+        let stats = process_csv_to_json_file(&input_path, &output_path).unwrap();
+
+        assert_eq!(stats.clients, 1);
+        assert_eq!(
+            fs::read_to_string(&output_path).unwrap(),
+            r#"[{"client":1,"available":"1.5","held":"0","total":"1.5","locked":false}]"#
+        );
+
+        fs::remove_dir_all(&output_dir).unwrap();
+    }
+
+    #[test]
+    fn test_only_transaction_types() {
+        let config = ProcessingConfig {
+            only_transaction_types: Some(
+                TransactionTypeFilter::parse_list("deposit, dispute").unwrap(),
+            ),
+            ..Default::default()
+        };
+        let report = process_stream_with_config(
+            "type,client,tx,amount\n\
+             deposit,1,1,2\n\
+             withdrawal,1,2,1\n\
+             refund,1,3,1\n\
+             dispute,1,1,\n"
+                .as_bytes(),
+            InputFormat::Csv,
+            &config,
+        )
+        .unwrap();
+
+        assert_eq!(
+            report.client_states[&1.into()].held,
+            AmountType::from_str_exact("2").unwrap()
+        );
+        assert_eq!(report.transaction_type_stats.withdrawals, 1);
+        assert_eq!(
+            report.warnings,
+            vec![ProcessingWarning::FilteredByType {
+                client: 1.into(),
+                tx: 2.into(),
+                source_line: Some(3),
+            }]
+        );
+        assert_eq!(report.transaction_type_stats.unknown, 1);
+        assert!(matches!(
+            TransactionTypeFilter::parse_list("deposit,refund"),
+            Err(ProcessingError::UnknownTransactionType(_))
+        ));
+    }
+
+    #[test]
+    fn test_warning_source_lines() {
+        let warning_lines = |input: &str| {
+            process_stream_with_config(input.as_bytes(), InputFormat::Auto, &Default::default())
+                .unwrap()
+                .warnings
+                .iter()
+                .map(ProcessingWarning::source_line)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            warning_lines(
+                "type,client,tx,amount\n\
+                 deposit,1,1,1\n\
+                 dispute,1,2,\n\
+                 withdrawal,1,3,abc\n\
+                 chargeback,1,4,\n"
+            ),
+            vec![Some(3), Some(5)]
+        );
+        assert_eq!(
+            warning_lines(
+                "{\"type\":\"dispute\",\"client\":1,\"tx\":2}\n\
+                 \n\
+                 {\"type\":\"resolve\",\"client\":1,\"tx\":2}\n\
+                 [{\"type\":\"resolve\",\"client\":1,\"tx\":2}]"
+            ),
+            vec![Some(1), Some(3), None]
+        );
+    }
+
+    #[test]
+    fn test_precision_loss_warning() {
+        let report = process_stream_with_config(
+            "type,client,tx,amount\n\
+             deposit,1,1,1.23456\n\
+             withdrawal,1,2,0.5000\n\
+             dispute,1,3,\n"
+                .as_bytes(),
+            InputFormat::Csv,
+            &Default::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            report.warnings,
+            vec![
+                ProcessingWarning::PrecisionLoss {
+                    client: 1.into(),
+                    tx: 1.into(),
+                    original: AmountType::from_str_exact("1.23456").unwrap(),
+                    rounded: AmountType::from_str_exact("1.2346").unwrap(),
+                    source_line: Some(2),
+                },
+                ProcessingWarning::UnknownTransaction {
+                    client: 1.into(),
+                    tx: 3.into(),
+                    source_line: Some(4),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_leading_blank_lines() {
+        let report = process_stream_with_config(
+            "\n\ntype,client,tx,amount\n\
+             deposit,1,1,2\n\
+             dispute,1,2,\n"
+                .as_bytes(),
+            InputFormat::Csv,
+            &Default::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            report.client_states[&1.into()].available,
+            AmountType::from(2)
+        );
+        assert_eq!(
+            report.warnings,
+            vec![ProcessingWarning::UnknownTransaction {
+                client: 1.into(),
+                tx: 2.into(),
+                source_line: Some(5),
+            }]
+        );
+        assert!(matches!(
+            count_csv_transactions("\ntype,client,tx\n".as_bytes()),
+            Err(ProcessingError::MissingColumn(_))
+        ));
+    }
+
+    #[test]
+    fn test_strict_amounts_as_written() {
+        let input = "type,client,tx,amount\n\
+                     deposit,1,1,10.12346\n\
+                     deposit,1,2,10.1000\n\
+                     deposit,1,3,10.1\n";
+        let report = |decimal_places| {
+            process_stream_with_config(
+                input.as_bytes(),
+                InputFormat::Csv,
+                &ProcessingConfig {
+                    strict_amounts: Some(StrictAmountValidator { decimal_places }),
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+        };
+
+        let four_places = report(4);
+        assert_eq!(
+            four_places.client_states[&1.into()].available,
+            AmountType::from_str_exact("10.1").unwrap()
+        );
+        assert_eq!(four_places.stats.records_rejected, 2);
+        assert!(four_places.warnings.is_empty());
+
+        // More places than are kept: accepted as written, then rounded.
+        let five_places = report(5);
+        assert_eq!(
+            five_places.client_states[&1.into()].available,
+            AmountType::from_str_exact("10.1235").unwrap()
+        );
+        assert_eq!(five_places.stats.records_rejected, 2);
+        assert!(matches!(
+            five_places.warnings[..],
+            [ProcessingWarning::PrecisionLoss { .. }]
+        ));
+    }
 
-    Ok(csv_output)
+    #[test]
+    fn test_count_csv_transactions() {
+        let input = "type,client,tx,amount\n\
+                     deposit,1,1,1\n\
+                     deposit,1,2,nan\n\
+                     dispute,1,1,\n";
+        assert_eq!(count_csv_transactions(input.as_bytes()).unwrap(), 2);
+        assert_eq!(count_csv_transactions_fast(input.as_bytes()), 3);
+        let unterminated = input.trim_end();
+        assert_eq!(count_csv_transactions(unterminated.as_bytes()).unwrap(), 2);
+        assert_eq!(count_csv_transactions_fast(unterminated.as_bytes()), 3);
+        assert_eq!(
+            count_csv_transactions_fast("type,client,tx,amount".as_bytes()),
+            0
+        );
+        assert_eq!(count_csv_transactions_fast("".as_bytes()), 0);
+        assert!(matches!(
+            count_csv_transactions("type,client,tx\n".as_bytes()),
+            Err(ProcessingError::MissingColumn(_))
+        ));
+    }
 }
 
-mod transaction_processing_logic;
\ No newline at end of file
+mod account_ledger;
+mod amount_delta;
+mod chained_reader;
+mod change_event;
+mod client_id;
+mod client_state_map;
+mod client_state_reports;
+#[cfg(feature = "admin-operations")]
+mod corrections;
+#[cfg(feature = "multi-currency")]
+mod currency;
+#[cfg(feature = "signals")]
+mod graceful_shutdown;
+mod ignore_reason;
+mod input_format;
+mod memory_estimate;
+mod merge;
+mod multi_client_processor;
+mod output_format;
+mod processing_config;
+mod processing_error;
+mod processing_mode;
+mod processing_report;
+mod processing_warning;
+#[cfg(feature = "protobuf")]
+mod protobuf;
+mod retry_reader;
+mod ring_buffer_transaction_source;
+mod sql_output;
+#[cfg(feature = "sqlite")]
+mod sqlite_output;
+#[cfg(any(test, feature = "test-helpers"))]
+mod test_helpers;
+mod transaction_filter;
+mod transaction_id;
+mod transaction_log;
+mod transaction_processing_logic;
+mod transaction_sequence_validator;
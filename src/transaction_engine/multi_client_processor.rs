@@ -0,0 +1,67 @@
+use super::transaction_processing_logic::process_transactions_and_return_client_states;
+use super::{ClientId, ClientState, Transaction};
+use std::collections::HashMap;
+use std::sync::mpsc;
+use std::thread;
+
+/// Processes the transactions on `num_shards` threads, each one for the clients whose id modulo `num_shards` is its
+/// shard number. The results match `process_transactions_and_return_client_states`, since a transaction only ever
+/// affects its own client, and one referencing another client's deposit is ignored either way. The one exception is
+/// a deposit that reuses the transaction id of another client's deposit: it no longer replaces the earlier one when
+/// the two clients are in different shards.
+#[derive(Debug, Clone, Copy)]
+pub struct MultiClientProcessor {
+    num_shards: usize,
+}
+
+impl MultiClientProcessor {
+    pub fn new(num_shards: usize) -> Self {
+        assert!(0 < num_shards, "at least one shard is required");
+        MultiClientProcessor { num_shards }
+    }
+
+    pub fn process(
+        self,
+        transactions: impl IntoIterator<Item = Transaction> + Send,
+    ) -> HashMap<ClientId, ClientState> {
+        thread::scope(|scope| {
+            let (senders, shards): (Vec<_>, Vec<_>) = (0..self.num_shards)
+                .map(|_| {
+                    let (sender, receiver) = mpsc::channel();
+                    let shard = scope
+                        .spawn(move || process_transactions_and_return_client_states(receiver));
+                    (sender, shard)
+                })
+                .unzip();
+
+            for transaction in transactions {
+                let shard = usize::from(u16::from(transaction.client)) % self.num_shards;
+                senders[shard]
+                    .send(transaction)
+                    .expect("the shard receives until the sender is dropped");
+            }
+            drop(senders);
+
+            shards
+                .into_iter()
+                .flat_map(|shard| shard.join().expect("a shard panicked"))
+                .collect()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_helpers::{assert_client_state_equals, random_transactions};
+    use super::*;
+
+    #[test]
+    fn test_multi_client_processor() {
+        let transactions = random_transactions(3, 50, 5000);
+
+        assert_client_state_equals(
+            &MultiClientProcessor::new(4).process(transactions.clone()),
+            &process_transactions_and_return_client_states(transactions),
+        );
+    }
+}
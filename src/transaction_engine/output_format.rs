@@ -0,0 +1,539 @@
+use super::{AmountType, ClientId, ClientState, OutputCsvRecord, ProcessingError};
+use csv::Writer;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::io::Write;
+use std::path::Path;
+use std::str::FromStr;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    Csv,
+    CsvGzip,
+    /// A JSON array with one object per client, using the CSV column names as keys.
+    Json,
+}
+
+impl OutputFormat {
+    /// `CsvGzip` for a `.csv.gz` extension, `Json` for `.json`, `Csv` otherwise. Any other `.gz` file, e.g.
+    /// `states.json.gz`, is an `UnknownOutputFormat` error rather than silently getting CSV.
+    pub fn from_path(path: &Path) -> Result<Self, ProcessingError> {
+        match path.extension() {
+            Some(extension) if extension == "gz" => {
+                let inner_extension = path
+                    .file_stem()
+                    .and_then(|file_stem| Path::new(file_stem).extension())
+                    .unwrap_or_default();
+                format!("{}.gz", inner_extension.to_string_lossy()).parse()
+            }
+            Some(extension) if extension == "json" => Ok(OutputFormat::Json),
+            _ => Ok(OutputFormat::Csv),
+        }
+    }
+}
+
+/// Accepts the file extensions recognized by `from_path`: `csv`, `csv.gz` and `json`.
+impl FromStr for OutputFormat {
+    type Err = ProcessingError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(OutputFormat::Csv),
+            "csv.gz" => Ok(OutputFormat::CsvGzip),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(ProcessingError::UnknownOutputFormat(s.to_owned())),
+        }
+    }
+}
+
+/// The version of the client states CSV format, bumped whenever its columns change.
+pub const CSV_SCHEMA_VERSION: u32 = 1;
+
+const SCHEMA_VERSION_PREFIX: &str = "# schema_version=";
+
+#[derive(Debug, Default, Clone)]
+pub struct WriterConfig {
+    /// Rounds `available` and `held` to this many decimal places in the output only, e.g. 2 for statements that show
+    /// cents, and writes their sum as `total`. `None` writes the amounts as they are.
+    pub display_decimal_places: Option<u32>,
+    /// Starts CSV output with a `# schema_version=N` comment line, so that readers can tell the format version apart.
+    /// Ignored for JSON.
+    pub include_schema_version: bool,
+    /// Ends CSV output with a `TOTAL` row holding the sums of `available`, `held` and `total`, and the number of locked
+    /// clients in the `locked` column, as in spreadsheet exports. The row doesn't parse as a client. Left out when there
+    /// are no clients, and ignored for JSON.
+    pub include_totals_row: bool,
+    /// Adds the per-type transaction counts of each client as extra columns.
+    #[cfg(feature = "detailed-stats")]
+    pub extended: bool,
+}
+
+impl WriterConfig {
+    fn column_count(&self) -> usize {
+        #[cfg(feature = "detailed-stats")]
+        if self.extended {
+            return 10;
+        }
+        5
+    }
+
+    fn apply(&self, (client_id, client_state): (ClientId, ClientState)) -> OutputCsvRecord {
+        #[cfg(feature = "detailed-stats")]
+        let counts = [
+            client_state.deposit_count,
+            client_state.withdrawal_count,
+            client_state.dispute_count,
+            client_state.resolve_count,
+            client_state.chargeback_count,
+        ];
+        let mut output_record = OutputCsvRecord::from((client_id, client_state));
+        #[cfg(feature = "detailed-stats")]
+        if self.extended {
+            let [deposits, withdrawals, disputes, resolves, chargebacks] = counts.map(Some);
+            output_record.deposit_count = deposits;
+            output_record.withdrawal_count = withdrawals;
+            output_record.dispute_count = disputes;
+            output_record.resolve_count = resolves;
+            output_record.chargeback_count = chargebacks;
+        }
+        if let Some(decimal_places) = self.display_decimal_places {
+            for amount in [&mut output_record.available, &mut output_record.held] {
+                *amount = amount.round_dp(decimal_places);
+                amount.rescale(decimal_places);
+            }
+            // Summed from the rounded amounts, so that the row still adds up.
+            output_record.total = output_record.available + output_record.held;
+        }
+        output_record
+    }
+}
+
+pub fn write_client_states(
+    clients: impl IntoIterator<Item = (ClientId, ClientState)>,
+    output: impl Write,
+    format: OutputFormat,
+) -> Result<(), ProcessingError> {
+    write_client_states_with_config(clients, output, format, &WriterConfig::default())
+}
+
+pub fn write_client_states_with_config(
+    clients: impl IntoIterator<Item = (ClientId, ClientState)>,
+    output: impl Write,
+    format: OutputFormat,
+    config: &WriterConfig,
+) -> Result<(), ProcessingError> {
+    let output_records = clients.into_iter().map(|client| config.apply(client));
+    match format {
+        OutputFormat::Csv => write_csv(output_records, output, config)?.flush()?,
+        OutputFormat::CsvGzip => {
+            let mut encoder = GzEncoder::new(output, Compression::default());
+            if config.include_schema_version {
+                write_schema_version(&mut encoder)?;
+            }
+            let mut writer = GzipCsvWriter(Writer::from_writer(encoder));
+            write_csv_records(&mut writer.0, output_records, config)?;
+            writer.finish()?.flush()?
+        }
+        OutputFormat::Json => write_json(output_records, output)?,
+    }
+    Ok(())
+}
+
+fn write_csv<W: Write>(
+    output_records: impl Iterator<Item = OutputCsvRecord>,
+    mut output: W,
+    config: &WriterConfig,
+) -> Result<W, ProcessingError> {
+    if config.include_schema_version {
+        write_schema_version(&mut output)?;
+    }
+    let mut writer = Writer::from_writer(output);
+    write_csv_records(&mut writer, output_records, config)?;
+    writer.into_inner().map_err(|e| e.into_error().into())
+}
+
+fn write_csv_records<W: Write>(
+    writer: &mut Writer<W>,
+    output_records: impl Iterator<Item = OutputCsvRecord>,
+    config: &WriterConfig,
+) -> Result<(), ProcessingError> {
+    let mut totals = TotalsRow::default();
+    let mut has_clients = false;
+    for output_record in output_records {
+        totals.add(&output_record);
+        writer.serialize(output_record)?;
+        has_clients = true;
+    }
+    // The header is only written along with the first client, and the row would be left without one.
+    if config.include_totals_row && has_clients {
+        totals.write(writer, config)?;
+    }
+    Ok(())
+}
+
+#[derive(Default)]
+struct TotalsRow {
+    available: AmountType,
+    held: AmountType,
+    total: AmountType,
+    locked_count: usize,
+}
+
+impl TotalsRow {
+    fn add(&mut self, output_record: &OutputCsvRecord) {
+        self.available += output_record.available;
+        self.held += output_record.held;
+        self.total += output_record.total;
+        self.locked_count += usize::from(output_record.locked);
+    }
+
+    fn write<W: Write>(
+        &self,
+        writer: &mut Writer<W>,
+        config: &WriterConfig,
+    ) -> Result<(), ProcessingError> {
+        let mut record = vec![
+            "TOTAL".to_owned(),
+            self.available.to_string(),
+            self.held.to_string(),
+            self.total.to_string(),
+            self.locked_count.to_string(),
+        ];
+        // The per-type counts aren't summed.
+        record.resize(config.column_count(), String::new());
+        writer.write_record(record)?;
+        Ok(())
+    }
+}
+
+fn write_schema_version(mut output: impl Write) -> Result<(), ProcessingError> {
+    writeln!(output, "{SCHEMA_VERSION_PREFIX}{CSV_SCHEMA_VERSION}")?;
+    Ok(())
+}
+
+/// Writes client states as gzip-compressed CSV one at a time, e.g. for dumps too large to collect first. `finish` must
+/// be called to complete the gzip stream.
+pub struct GzipCsvWriter<W: Write>(Writer<GzEncoder<W>>);
+
+impl<W: Write> GzipCsvWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self(Writer::from_writer(GzEncoder::new(
+            inner,
+            Compression::default(),
+        )))
+    }
+
+    pub fn serialize_client_state(
+        &mut self,
+        client: (ClientId, ClientState),
+    ) -> Result<(), ProcessingError> {
+        self.serialize(client.into())
+    }
+
+    fn serialize(&mut self, output_record: OutputCsvRecord) -> Result<(), ProcessingError> {
+        self.0.serialize(output_record)?;
+        Ok(())
+    }
+
+    /// Flushes the CSV and completes the gzip stream, returning the underlying writer.
+    pub fn finish(self) -> Result<W, ProcessingError> {
+        let encoder = self.0.into_inner().map_err(|e| e.into_error())?;
+        Ok(encoder.finish()?)
+    }
+}
+
+/// Splits the optional schema version line off client states CSV. Output written before the line existed has no
+/// version and is the same as version 1. Versions newer than `CSV_SCHEMA_VERSION` are rejected.
+pub(super) fn strip_schema_version(csv: &str) -> Result<(u32, &str), ProcessingError> {
+    let Some(rest) = csv.strip_prefix(SCHEMA_VERSION_PREFIX) else {
+        return Ok((1, csv));
+    };
+    let (version, rest) = rest.split_once('\n').unwrap_or((rest, ""));
+    let version = version
+        .trim_end()
+        .parse()
+        .map_err(|_| ProcessingError::UnsupportedSchemaVersion(version.to_owned()))?;
+    if CSV_SCHEMA_VERSION < version {
+        return Err(ProcessingError::UnsupportedSchemaVersion(
+            version.to_string(),
+        ));
+    }
+    Ok((version, rest))
+}
+
+/// Writes a JSON array of client states one entry at a time, so that the states never need to be held in memory
+/// simultaneously.
+pub fn write_client_states_as_json_stream(
+    clients: impl IntoIterator<Item = (ClientId, ClientState)>,
+    output: impl Write,
+) -> Result<(), ProcessingError> {
+    write_json(clients.into_iter().map(Into::into), output)
+}
+
+fn write_json(
+    output_records: impl Iterator<Item = OutputCsvRecord>,
+    mut output: impl Write,
+) -> Result<(), ProcessingError> {
+    output.write_all(b"[")?;
+    for (i, output_record) in output_records.enumerate() {
+        if 0 < i {
+            output.write_all(b",")?;
+        }
+        serde_json::to_writer(&mut output, &output_record)?;
+    }
+    output.write_all(b"]")?;
+    output.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::AmountType;
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    #[test]
+    fn test_gzip_output() {
+        let mut compressed = Vec::new();
+        write_client_states(
+            [(
+                1.into(),
+                ClientState {
+                    available: AmountType::from_str_exact("1.5").unwrap(),
+                    held: AmountType::ZERO,
+                    locked: false,
+                    ..Default::default()
+                },
+            )],
+            &mut compressed,
+            OutputFormat::from_path(Path::new("output.csv.gz")).unwrap(),
+        )
+        .unwrap();
+
+        let mut decompressed = String::new();
+        GzDecoder::new(compressed.as_slice())
+            .read_to_string(&mut decompressed)
+            .unwrap();
+        assert_eq!(
+            decompressed,
+            "client,available,held,total,locked\n1,1.5,0,1.5,false\n"
+        );
+    }
+
+    #[test]
+    fn test_output_format_from_path() {
+        for (path, format) in [
+            ("states.csv", OutputFormat::Csv),
+            ("states", OutputFormat::Csv),
+            ("states.csv.gz", OutputFormat::CsvGzip),
+            ("states.json", OutputFormat::Json),
+        ] {
+            assert_eq!(OutputFormat::from_path(Path::new(path)).unwrap(), format);
+        }
+        for (path, format) in [("states.json.gz", "json.gz"), ("states.gz", ".gz")] {
+            assert!(matches!(
+                OutputFormat::from_path(Path::new(path)),
+                Err(ProcessingError::UnknownOutputFormat(unknown)) if unknown == format
+            ));
+        }
+    }
+
+    #[test]
+    fn test_gzip_csv_writer() {
+        let mut writer = GzipCsvWriter::new(Vec::new());
+        for client_id in [1, 2] {
+            writer
+                .serialize_client_state((client_id.into(), ClientState::default()))
+                .unwrap();
+        }
+        let compressed = writer.finish().unwrap();
+
+        let mut decompressed = String::new();
+        GzDecoder::new(compressed.as_slice())
+            .read_to_string(&mut decompressed)
+            .unwrap();
+        assert_eq!(
+            decompressed,
+            "client,available,held,total,locked\n1,0,0,0,false\n2,0,0,0,false\n"
+        );
+    }
+
+    #[test]
+    fn test_json_stream_output() {
+        let mut output = Vec::new();
+        write_client_states_as_json_stream(
+            [
+                (
+                    1.into(),
+                    ClientState {
+                        available: AmountType::from_str_exact("1.5").unwrap(),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    2.into(),
+                    ClientState {
+                        held: AmountType::from_str_exact("2").unwrap(),
+                        locked: true,
+                        ..Default::default()
+                    },
+                ),
+            ],
+            &mut output,
+        )
+        .unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            r#"[{"client":1,"available":"1.5","held":"0","total":"1.5","locked":false},{"client":2,"available":"0","held":"2","total":"2","locked":true}]"#
+        );
+
+        let mut output = Vec::new();
+        write_client_states_as_json_stream([], &mut output).unwrap();
+        assert_eq!(output, b"[]");
+    }
+
+    #[test]
+    fn test_display_decimal_places() {
+        let mut output = Vec::new();
+        let client = |available: &str, held: &str| ClientState {
+            available: AmountType::from_str_exact(available).unwrap(),
+            held: AmountType::from_str_exact(held).unwrap(),
+            ..Default::default()
+        };
+        write_client_states_with_config(
+            [
+                (1.into(), client("1.2345", "2")),
+                // Both round down to even, so rounding the total on its own would give 0.01.
+                (2.into(), client("0.005", "0.005")),
+            ],
+            &mut output,
+            OutputFormat::Csv,
+            &WriterConfig {
+                display_decimal_places: Some(2),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "client,available,held,total,locked\n\
+             1,1.23,2.00,3.23,false\n\
+             2,0.00,0.00,0.00,false\n"
+        );
+    }
+
+    #[test]
+    fn test_totals_row() {
+        let client = |available: &str, locked| ClientState {
+            available: AmountType::from_str_exact(available).unwrap(),
+            held: 1.into(),
+            locked,
+            ..Default::default()
+        };
+        let mut output = Vec::new();
+        write_client_states_with_config(
+            [
+                (1.into(), client("1.5", false)),
+                (2.into(), client("2.25", true)),
+            ],
+            &mut output,
+            OutputFormat::Csv,
+            &WriterConfig {
+                include_totals_row: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "client,available,held,total,locked\n\
+             1,1.5,1,2.5,false\n\
+             2,2.25,1,3.25,true\n\
+             TOTAL,3.75,2,5.75,1\n"
+        );
+
+        let mut output = Vec::new();
+        write_client_states_with_config(
+            [],
+            &mut output,
+            OutputFormat::Csv,
+            &WriterConfig {
+                include_totals_row: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn test_schema_version() {
+        let mut output = Vec::new();
+        write_client_states_with_config(
+            [(1.into(), ClientState::default())],
+            &mut output,
+            OutputFormat::Csv,
+            &WriterConfig {
+                include_schema_version: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(
+            output,
+            "# schema_version=1\nclient,available,held,total,locked\n1,0,0,0,false\n"
+        );
+
+        assert_eq!(
+            strip_schema_version(&output).unwrap(),
+            (1, "client,available,held,total,locked\n1,0,0,0,false\n")
+        );
+        assert_eq!(strip_schema_version("client\n").unwrap(), (1, "client\n"));
+        assert!(matches!(
+            strip_schema_version("# schema_version=2\nclient\n"),
+            Err(ProcessingError::UnsupportedSchemaVersion(_))
+        ));
+    }
+
+    #[cfg(feature = "detailed-stats")]
+    #[test]
+    fn test_extended_output() {
+        let clients = super::super::transaction_processing_logic::process_transactions_and_return_client_states(
+            super::super::random_transactions(7, 1, 20),
+        );
+        let client_state = &clients[&1.into()];
+        let expected_counts = format!(
+            "{},{},{},{},{}",
+            client_state.deposit_count,
+            client_state.withdrawal_count,
+            client_state.dispute_count,
+            client_state.resolve_count,
+            client_state.chargeback_count
+        );
+
+        let mut output = Vec::new();
+        write_client_states_with_config(
+            clients,
+            &mut output,
+            OutputFormat::Csv,
+            &WriterConfig {
+                extended: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let mut lines = output.lines();
+        assert_eq!(
+            lines.next().unwrap(),
+            "client,available,held,total,locked,deposit_count,withdrawal_count,dispute_count,resolve_count,chargeback_count"
+        );
+        assert!(lines.next().unwrap().ends_with(&expected_counts));
+    }
+}
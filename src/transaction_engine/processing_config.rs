@@ -0,0 +1,52 @@
+use super::{AmountType, TransactionTypeFilter};
+use std::time::Duration;
+
+/// What to do when a dispute, resolve or chargeback references a transaction that has no deposit on record.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum UnknownTransactionPolicy {
+    /// Only log the transaction to stderr.
+    Ignore,
+    /// Log the transaction and report a `ProcessingWarning`.
+    #[default]
+    Warn,
+    /// Stop processing and return an error.
+    Error,
+}
+
+/// Rejects amounts that aren't written with exactly `decimal_places` decimal places, e.g. a deposit of `10.1` has to be
+/// written as `10.1000` with the default of 4. Input read by `process_stream_with_config` is checked as written, before
+/// amounts with more than 4 decimal places are rounded, and the records it rejects count as `records_rejected`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StrictAmountValidator {
+    pub decimal_places: u32,
+}
+
+impl Default for StrictAmountValidator {
+    fn default() -> Self {
+        StrictAmountValidator { decimal_places: 4 }
+    }
+}
+
+impl StrictAmountValidator {
+    pub fn accepts(&self, amount: AmountType) -> bool {
+        amount.scale() == self.decimal_places
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct ProcessingConfig {
+    pub unknown_transaction_policy: UnknownTransactionPolicy,
+    /// Record an `AuditEntry` for every transaction. Memory then grows with the number of transactions.
+    pub record_audit_entries: bool,
+    /// Fail on a deposit whose transaction id is already on record, instead of letting it replace the earlier one.
+    pub strict_mode: bool,
+    /// Only process transactions of these types; the others are skipped with a `FilteredByType` warning.
+    pub only_transaction_types: Option<Vec<TransactionTypeFilter>>,
+    /// How many times a failed read of the input is retried before giving up, see `RetryReader`.
+    pub max_io_retries: u32,
+    /// The wait before the first retry of a failed read, doubled for each further retry.
+    pub retry_delay: Duration,
+    /// Ignore deposits, withdrawals and corrections whose amounts the validator doesn't accept, see
+    /// `StrictAmountValidator` for input that is read rather than passed in as transactions.
+    pub strict_amounts: Option<StrictAmountValidator>,
+}
@@ -0,0 +1,162 @@
+use super::{AmountType, ClientId, TransactionId};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+#[derive(Debug)]
+pub enum ProcessingError {
+    Io(std::io::Error),
+    Csv(csv::Error),
+    Json(serde_json::Error),
+    #[cfg(feature = "sqlite")]
+    Sqlite(rusqlite::Error),
+    #[cfg(feature = "protobuf")]
+    Protobuf(prost::DecodeError),
+    #[cfg(feature = "signals")]
+    Signal(ctrlc::Error),
+    /// A protobuf message decoded, but the named field holds a value that doesn't fit, e.g. a malformed amount.
+    #[cfg(feature = "protobuf")]
+    InvalidProtobufField(String),
+    InvalidFilenamePattern(String),
+    UnknownOutputFormat(String),
+    UnknownProcessingMode(String),
+    /// A table name for generated SQL that isn't a plain identifier.
+    InvalidTableName(String),
+    UnknownTransactionType(String),
+    /// The CSV header lacks a column that transaction records need.
+    MissingColumn(String),
+    UnknownTransaction {
+        client: ClientId,
+        tx: TransactionId,
+    },
+    /// A deposit reused the transaction id of an earlier one, in strict mode.
+    DuplicateTransactionId(TransactionId),
+    /// An account can't be closed while funds are held by open disputes.
+    FundsHeld(AmountType),
+    /// A backfill would cause a transaction that was applied to be ignored.
+    BackfillConflict(TransactionId),
+    /// The ledger has no history to backfill, because it was restored from a snapshot or not created with
+    /// `AccountLedger::with_history`.
+    HistoryUnavailable,
+    /// A transaction would corrupt the state, e.g. by overwriting a deposit on record; see `AccountLedger::apply_batch`.
+    FatalError(String),
+    /// The schema version of a client states CSV is malformed or newer than this version supports.
+    UnsupportedSchemaVersion(String),
+}
+
+impl Display for ProcessingError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ProcessingError::Io(e) => write!(f, "io error: {e}"),
+            ProcessingError::Csv(e) => write!(f, "csv error: {e}"),
+            ProcessingError::Json(e) => write!(f, "json error: {e}"),
+            #[cfg(feature = "sqlite")]
+            ProcessingError::Sqlite(e) => write!(f, "sqlite error: {e}"),
+            #[cfg(feature = "protobuf")]
+            ProcessingError::Protobuf(e) => write!(f, "protobuf error: {e}"),
+            #[cfg(feature = "signals")]
+            ProcessingError::Signal(e) => write!(f, "signal handler error: {e}"),
+            #[cfg(feature = "protobuf")]
+            ProcessingError::InvalidProtobufField(field) => {
+                write!(f, "{field:?}: invalid protobuf field value")
+            }
+            ProcessingError::InvalidFilenamePattern(pattern) => {
+                write!(
+                    f,
+                    "{pattern:?}: filename pattern must contain {{client_id}}"
+                )
+            }
+            ProcessingError::UnknownOutputFormat(format) => {
+                write!(f, "{format:?}: unknown output format")
+            }
+            ProcessingError::UnknownProcessingMode(mode) => {
+                write!(f, "{mode:?}: unknown processing mode")
+            }
+            ProcessingError::InvalidTableName(table_name) => {
+                write!(
+                    f,
+                    "{table_name:?}: table name must be a plain SQL identifier"
+                )
+            }
+            ProcessingError::MissingColumn(column) => write!(f, "missing column {column:?}"),
+            ProcessingError::UnknownTransactionType(transaction_type) => {
+                write!(f, "{transaction_type:?}: unknown transaction type")
+            }
+            ProcessingError::UnknownTransaction { client, tx } => {
+                write!(f, "client {client} referenced unknown transaction {tx}")
+            }
+            ProcessingError::DuplicateTransactionId(tx) => {
+                write!(f, "transaction {tx} is a duplicate deposit")
+            }
+            ProcessingError::FundsHeld(held) => write!(f, "{held} is still held by open disputes"),
+            ProcessingError::BackfillConflict(tx) => {
+                write!(
+                    f,
+                    "transaction {tx} would no longer apply after the backfill"
+                )
+            }
+            ProcessingError::HistoryUnavailable => {
+                write!(f, "the ledger has no transaction history to backfill")
+            }
+            ProcessingError::FatalError(message) => write!(f, "fatal error: {message}"),
+            ProcessingError::UnsupportedSchemaVersion(version) => {
+                write!(f, "{version:?}: unsupported schema version")
+            }
+        }
+    }
+}
+
+impl Error for ProcessingError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ProcessingError::Io(e) => Some(e),
+            ProcessingError::Csv(e) => Some(e),
+            ProcessingError::Json(e) => Some(e),
+            #[cfg(feature = "sqlite")]
+            ProcessingError::Sqlite(e) => Some(e),
+            #[cfg(feature = "protobuf")]
+            ProcessingError::Protobuf(e) => Some(e),
+            #[cfg(feature = "signals")]
+            ProcessingError::Signal(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for ProcessingError {
+    fn from(e: std::io::Error) -> Self {
+        ProcessingError::Io(e)
+    }
+}
+
+impl From<csv::Error> for ProcessingError {
+    fn from(e: csv::Error) -> Self {
+        ProcessingError::Csv(e)
+    }
+}
+
+impl From<serde_json::Error> for ProcessingError {
+    fn from(e: serde_json::Error) -> Self {
+        ProcessingError::Json(e)
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl From<rusqlite::Error> for ProcessingError {
+    fn from(e: rusqlite::Error) -> Self {
+        ProcessingError::Sqlite(e)
+    }
+}
+
+#[cfg(feature = "protobuf")]
+impl From<prost::DecodeError> for ProcessingError {
+    fn from(e: prost::DecodeError) -> Self {
+        ProcessingError::Protobuf(e)
+    }
+}
+
+#[cfg(feature = "signals")]
+impl From<ctrlc::Error> for ProcessingError {
+    fn from(e: ctrlc::Error) -> Self {
+        ProcessingError::Signal(e)
+    }
+}
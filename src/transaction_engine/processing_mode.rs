@@ -0,0 +1,141 @@
+use super::input_format::{parse_csv_line, REQUIRED_COLUMNS};
+use super::{
+    read_transactions, AccountLedger, ClientId, InputFormat, OutputCsvRecord, ProcessingError,
+    ProcessingStats,
+};
+use csv::{StringRecord, Writer};
+use std::io::{BufRead, Read, Write};
+use std::str::FromStr;
+
+/// How the command line tool processes its input, see `--mode`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessingMode {
+    /// Processes all the input, then writes all the client states.
+    #[default]
+    Batch,
+    /// Writes the state of a client every time a transaction of it is processed, see `process_streaming`.
+    Streaming,
+    /// Processes transactions typed in line by line, see `process_interactive`.
+    Interactive,
+}
+
+impl FromStr for ProcessingMode {
+    type Err = ProcessingError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "batch" => Ok(ProcessingMode::Batch),
+            "streaming" => Ok(ProcessingMode::Streaming),
+            "interactive" => Ok(ProcessingMode::Interactive),
+            _ => Err(ProcessingError::UnknownProcessingMode(s.to_owned())),
+        }
+    }
+}
+
+/// Processes the transactions one at a time and writes the resulting state of the client after each of them, as a
+/// CSV row that is flushed right away. A client thus appears once per transaction, the last row being its final state.
+pub fn process_streaming(
+    input: impl Read,
+    input_format: InputFormat,
+    output: impl Write,
+) -> Result<ProcessingStats, ProcessingError> {
+    let mut stats = ProcessingStats::default();
+    let mut ledger = AccountLedger::new();
+    let mut writer = Writer::from_writer(output);
+    for (transaction, _) in read_transactions(input, input_format, &mut stats)? {
+        let client_id = transaction.client;
+        ledger.append(transaction);
+        write_client_state(&mut writer, &ledger, client_id)?;
+    }
+    Ok(stats)
+}
+
+/// Reads CSV transactions line by line, e.g. from a terminal, and answers each with the resulting state of its client
+/// as a CSV row. The lines have the columns `type,client,tx,amount` without a header line; one that is typed anyway
+/// is skipped. Malformed lines are logged to stderr and skipped.
+pub fn process_interactive(input: impl BufRead, output: impl Write) -> Result<(), ProcessingError> {
+    let headers = StringRecord::from(REQUIRED_COLUMNS.to_vec());
+    let mut ledger = AccountLedger::new();
+    let mut writer = Writer::from_writer(output);
+    for line in input.lines() {
+        let line = line?;
+        if line.split(',').next().map(str::trim) == Some(REQUIRED_COLUMNS[0]) {
+            continue;
+        }
+        let Some(transaction) = parse_csv_line(line.as_bytes(), &headers) else {
+            continue;
+        };
+        let client_id = transaction.client;
+        ledger.append(transaction);
+        write_client_state(&mut writer, &ledger, client_id)?;
+    }
+    Ok(())
+}
+
+fn write_client_state(
+    writer: &mut Writer<impl Write>,
+    ledger: &AccountLedger,
+    client_id: ClientId,
+) -> Result<(), ProcessingError> {
+    let client_state = ledger
+        .client_state(client_id)
+        .expect("appending a transaction creates its client")
+        .clone();
+    writer.serialize(OutputCsvRecord::from((client_id, client_state)))?;
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_process_streaming() {
+        let mut output = Vec::new();
+        process_streaming(
+            "type,client,tx,amount\n\
+             deposit,1,1,2\n\
+             deposit,2,2,1\n\
+             withdrawal,1,3,0.5\n"
+                .as_bytes(),
+            InputFormat::Csv,
+            &mut output,
+        )
+        .unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "client,available,held,total,locked\n\
+             1,2,0,2,false\n\
+             2,1,0,1,false\n\
+             1,1.5,0,1.5,false\n"
+        );
+    }
+
+    #[test]
+    fn test_process_interactive() {
+        let mut output = Vec::new();
+        process_interactive(
+            "type,client,tx,amount\n\
+             deposit,1,1,2\n\
+             bogus\n\
+             dispute,1,1,\n"
+                .as_bytes(),
+            &mut output,
+        )
+        .unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "client,available,held,total,locked\n\
+             1,2,0,2,false\n\
+             1,0,2,2,false\n"
+        );
+        assert_eq!(
+            "streaming".parse::<ProcessingMode>().unwrap(),
+            ProcessingMode::Streaming
+        );
+        assert!("fast".parse::<ProcessingMode>().is_err());
+    }
+}
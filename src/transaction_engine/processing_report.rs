@@ -0,0 +1,112 @@
+use super::transaction_processing_logic::{
+    CHARGEBACK, CORRECTION, DEPOSIT, DISPUTE, RESOLVE, WITHDRAWAL,
+};
+use super::{
+    AmountDelta, ClientStateMap, FlowTotals, IgnoreReason, ProcessingStats, ProcessingWarning,
+    Transaction, TransactionType,
+};
+use std::collections::HashMap;
+
+/// The effect of one transaction on its client's balances.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditEntry {
+    pub transaction: Transaction,
+    /// `None` if the transaction was applied.
+    pub ignore_reason: Option<IgnoreReason>,
+    pub available_delta: AmountDelta,
+    pub held_delta: AmountDelta,
+}
+
+/// How many transactions of each type a processing run has seen, whether they were applied or not.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TransactionTypeStats {
+    pub deposits: u64,
+    pub withdrawals: u64,
+    pub disputes: u64,
+    pub resolves: u64,
+    pub chargebacks: u64,
+    pub corrections: u64,
+    /// Input records with an unrecognized type. These never become transactions, so only the functions that read the
+    /// input themselves, like `process_stream_with_config`, can count them.
+    pub unknown: u64,
+}
+
+impl TransactionTypeStats {
+    pub(super) fn record(&mut self, tx_type: &TransactionType) {
+        match tx_type {
+            TransactionType::Deposit(_) => self.deposits += 1,
+            TransactionType::Withdrawal(_) => self.withdrawals += 1,
+            TransactionType::Dispute => self.disputes += 1,
+            TransactionType::Resolve => self.resolves += 1,
+            TransactionType::Chargeback => self.chargebacks += 1,
+            TransactionType::Correction(..) => self.corrections += 1,
+        }
+    }
+
+    /// The share of each type in percent, keyed by the type names of the input plus `"unknown"`, e.g. to spot a
+    /// workload with 40% disputes. All shares are 0 when nothing was seen.
+    pub fn transaction_type_breakdown_pct(&self) -> HashMap<&'static str, f64> {
+        let counts = [
+            (DEPOSIT, self.deposits),
+            (WITHDRAWAL, self.withdrawals),
+            (DISPUTE, self.disputes),
+            (RESOLVE, self.resolves),
+            (CHARGEBACK, self.chargebacks),
+            (CORRECTION, self.corrections),
+            ("unknown", self.unknown),
+        ];
+        let total: u64 = counts.iter().map(|&(_, count)| count).sum();
+        counts
+            .into_iter()
+            .map(|(name, count)| {
+                let pct = if total == 0 {
+                    0.0
+                } else {
+                    count as f64 * 100.0 / total as f64
+                };
+                (name, pct)
+            })
+            .collect()
+    }
+}
+
+/// Everything gathered by a single processing run; the other `process_transactions_*` functions hand out the parts
+/// their callers need.
+#[derive(Debug, Default, PartialEq)]
+pub struct ProcessingReport {
+    pub client_states: ClientStateMap,
+    pub stats: ProcessingStats,
+    pub warnings: Vec<ProcessingWarning>,
+    /// Only filled in when `ProcessingConfig::record_audit_entries` is set.
+    pub audit_entries: Vec<AuditEntry>,
+    pub deposit_totals: FlowTotals,
+    pub withdrawal_totals: FlowTotals,
+    pub transaction_type_stats: TransactionTypeStats,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transaction_type_breakdown_pct() {
+        let stats = TransactionTypeStats {
+            deposits: 5,
+            withdrawals: 2,
+            disputes: 2,
+            unknown: 1,
+            ..Default::default()
+        };
+        let breakdown = stats.transaction_type_breakdown_pct();
+
+        assert_eq!(breakdown.len(), 7);
+        assert_eq!(breakdown["deposit"], 50.0);
+        assert_eq!(breakdown["dispute"], 20.0);
+        assert_eq!(breakdown["chargeback"], 0.0);
+        assert_eq!(breakdown["unknown"], 10.0);
+        assert!(TransactionTypeStats::default()
+            .transaction_type_breakdown_pct()
+            .values()
+            .all(|&pct| pct == 0.0));
+    }
+}
@@ -0,0 +1,98 @@
+use super::{AmountType, ClientId, TransactionId};
+use std::fmt::{self, Display, Formatter};
+
+/// A non-fatal problem found during processing that the caller may want to act upon. `source_line` is the 1-based
+/// line of the input the transaction was read from, when processing a stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProcessingWarning {
+    /// A dispute, resolve or chargeback referenced a transaction with no deposit on record.
+    UnknownTransaction {
+        client: ClientId,
+        tx: TransactionId,
+        source_line: Option<u64>,
+    },
+    /// A transaction was skipped because its type is not in `ProcessingConfig::only_transaction_types`.
+    FilteredByType {
+        client: ClientId,
+        tx: TransactionId,
+        source_line: Option<u64>,
+    },
+    /// A transaction that arrived while its client was locked was applied after the lock was lifted.
+    ProcessedAfterUnlock { client: ClientId, tx: TransactionId },
+    /// An amount had more decimal places than supported and was rounded when the transaction was read. It reaches the
+    /// caller only through `ProcessingStats::read_warnings` or the report of `process_stream_with_config`; the readers
+    /// that return neither, e.g. `merge_csv_files` or the `TryFrom` conversion of a single record, round silently.
+    PrecisionLoss {
+        client: ClientId,
+        tx: TransactionId,
+        original: AmountType,
+        rounded: AmountType,
+        source_line: Option<u64>,
+    },
+}
+
+impl ProcessingWarning {
+    pub fn client(&self) -> ClientId {
+        match self {
+            ProcessingWarning::UnknownTransaction { client, .. }
+            | ProcessingWarning::FilteredByType { client, .. }
+            | ProcessingWarning::ProcessedAfterUnlock { client, .. }
+            | ProcessingWarning::PrecisionLoss { client, .. } => *client,
+        }
+    }
+
+    pub fn tx(&self) -> TransactionId {
+        match self {
+            ProcessingWarning::UnknownTransaction { tx, .. }
+            | ProcessingWarning::FilteredByType { tx, .. }
+            | ProcessingWarning::ProcessedAfterUnlock { tx, .. }
+            | ProcessingWarning::PrecisionLoss { tx, .. } => *tx,
+        }
+    }
+
+    /// A stable snake_case name of the variant, for machine-readable output.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ProcessingWarning::UnknownTransaction { .. } => "unknown_transaction",
+            ProcessingWarning::FilteredByType { .. } => "filtered_by_type",
+            ProcessingWarning::ProcessedAfterUnlock { .. } => "processed_after_unlock",
+            ProcessingWarning::PrecisionLoss { .. } => "precision_loss",
+        }
+    }
+
+    pub fn source_line(&self) -> Option<u64> {
+        match self {
+            ProcessingWarning::UnknownTransaction { source_line, .. }
+            | ProcessingWarning::FilteredByType { source_line, .. }
+            | ProcessingWarning::PrecisionLoss { source_line, .. } => *source_line,
+            ProcessingWarning::ProcessedAfterUnlock { .. } => None,
+        }
+    }
+}
+
+impl Display for ProcessingWarning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ProcessingWarning::UnknownTransaction { client, tx, .. } => {
+                write!(f, "{client:#} referenced unknown transaction {tx}")
+            }
+            ProcessingWarning::FilteredByType { tx, .. } => {
+                write!(f, "transaction {tx} skipped because of its type")
+            }
+            ProcessingWarning::ProcessedAfterUnlock { client, tx } => {
+                write!(f, "transaction {tx} applied after {client:#} was unlocked")
+            }
+            ProcessingWarning::PrecisionLoss {
+                tx,
+                original,
+                rounded,
+                ..
+            } => {
+                write!(
+                    f,
+                    "amount {original} of transaction {tx} rounded to {rounded}"
+                )
+            }
+        }
+    }
+}
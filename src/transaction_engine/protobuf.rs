@@ -0,0 +1,161 @@
+use super::transaction_processing_logic::{
+    CHARGEBACK, CORRECTION, DEPOSIT, DISPUTE, RESOLVE, WITHDRAWAL,
+};
+use super::{AmountType, ClientState, ProcessingError, Transaction, TransactionType};
+use prost::Message;
+
+// Protobuf encoding of transactions and client states, following `proto/transaction_engine.proto`. The message types
+// are written out with the `prost` derives rather than generated at build time, so that building doesn't need
+// `protoc`; keep them in sync with the schema.
+#[derive(Clone, PartialEq, Message)]
+struct TransactionMessage {
+    #[prost(uint32, tag = "1")]
+    client: u32,
+    #[prost(uint32, tag = "2")]
+    tx: u32,
+    #[prost(string, tag = "3")]
+    r#type: String,
+    #[prost(string, optional, tag = "4")]
+    amount: Option<String>,
+    #[prost(bool, tag = "5")]
+    force: bool,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct ClientStateMessage {
+    #[prost(string, tag = "1")]
+    available: String,
+    #[prost(string, tag = "2")]
+    held: String,
+    #[prost(bool, tag = "3")]
+    locked: bool,
+    #[prost(message, repeated, tag = "4")]
+    pending_locked_transactions: Vec<TransactionMessage>,
+}
+
+impl From<&Transaction> for TransactionMessage {
+    fn from(transaction: &Transaction) -> Self {
+        Self {
+            client: u16::from(transaction.client).into(),
+            tx: transaction.tx.into(),
+            r#type: transaction.tx_type.name().to_owned(),
+            amount: transaction
+                .tx_type
+                .amount()
+                .map(|amount| amount.to_string()),
+            force: matches!(transaction.tx_type, TransactionType::Correction(_, true)),
+        }
+    }
+}
+
+impl TryFrom<TransactionMessage> for Transaction {
+    type Error = ProcessingError;
+
+    fn try_from(message: TransactionMessage) -> Result<Self, Self::Error> {
+        let amount = || parse_amount("amount", message.amount.as_deref().unwrap_or_default());
+        let tx_type = match message.r#type.as_str() {
+            DEPOSIT => TransactionType::Deposit(amount()?),
+            WITHDRAWAL => TransactionType::Withdrawal(amount()?),
+            DISPUTE => TransactionType::Dispute,
+            RESOLVE => TransactionType::Resolve,
+            CHARGEBACK => TransactionType::Chargeback,
+            CORRECTION => TransactionType::Correction(amount()?, message.force),
+            _ => return Err(ProcessingError::UnknownTransactionType(message.r#type)),
+        };
+        let client = u16::try_from(message.client)
+            .map_err(|_| ProcessingError::InvalidProtobufField("client".to_owned()))?;
+        Ok(Self {
+            client: client.into(),
+            tx: message.tx.into(),
+            tx_type,
+        })
+    }
+}
+
+fn parse_amount(field: &str, amount: &str) -> Result<AmountType, ProcessingError> {
+    AmountType::from_str_exact(amount)
+        .map_err(|_| ProcessingError::InvalidProtobufField(field.to_owned()))
+}
+
+pub fn encode_transaction_to_proto(tx: &Transaction) -> Vec<u8> {
+    TransactionMessage::from(tx).encode_to_vec()
+}
+
+pub fn encode_client_state_to_proto(state: &ClientState) -> Vec<u8> {
+    ClientStateMessage {
+        available: state.available.to_string(),
+        held: state.held.to_string(),
+        locked: state.locked,
+        pending_locked_transactions: state
+            .pending_locked_transactions
+            .iter()
+            .map(Into::into)
+            .collect(),
+    }
+    .encode_to_vec()
+}
+
+/// The per-type transaction counts of `detailed-stats` aren't part of the encoding and decode as zero.
+pub fn decode_client_state_from_proto(bytes: &[u8]) -> Result<ClientState, ProcessingError> {
+    let message = ClientStateMessage::decode(bytes)?;
+    Ok(ClientState {
+        available: parse_amount("available", &message.available)?,
+        held: parse_amount("held", &message.held)?,
+        locked: message.locked,
+        pending_locked_transactions: message
+            .pending_locked_transactions
+            .into_iter()
+            .map(TryInto::try_into)
+            .collect::<Result<_, _>>()?,
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_state_round_trip() {
+        let client_state = ClientState {
+            available: AmountType::from_str_exact("1.5").unwrap(),
+            held: AmountType::from_str_exact("2.0001").unwrap(),
+            locked: true,
+            pending_locked_transactions: vec![Transaction {
+                client: 1.into(),
+                tx: 3.into(),
+                tx_type: TransactionType::Correction(
+                    AmountType::from_str_exact("-1").unwrap(),
+                    true,
+                ),
+            }],
+            ..Default::default()
+        };
+
+        let bytes = encode_client_state_to_proto(&client_state);
+        assert_eq!(
+            decode_client_state_from_proto(&bytes).unwrap(),
+            client_state
+        );
+        assert!(matches!(
+            decode_client_state_from_proto(&bytes[..bytes.len() - 1]),
+            Err(ProcessingError::Protobuf(_))
+        ));
+    }
+
+    #[test]
+    fn test_encode_transaction() {
+        let transaction = Transaction {
+            client: 1.into(),
+            tx: 2.into(),
+            tx_type: TransactionType::Dispute,
+        };
+
+        let message =
+            TransactionMessage::decode(encode_transaction_to_proto(&transaction).as_slice())
+                .unwrap();
+        assert_eq!(message.r#type, "dispute");
+        assert_eq!(message.amount, None);
+        assert_eq!(Transaction::try_from(message).unwrap(), transaction);
+    }
+}
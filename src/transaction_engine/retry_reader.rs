@@ -0,0 +1,90 @@
+use std::io::{self, Read};
+use std::thread::sleep;
+use std::time::Duration;
+
+/// Retries failed reads of the inner reader, e.g. a `TcpStream` with transient errors, waiting `retry_delay` before
+/// the first retry and doubling the wait before each further one. A read that fails doesn't consume any input, so
+/// retrying it neither skips nor duplicates records. The error is returned once `max_retries` consecutive retries have
+/// failed.
+pub struct RetryReader<R: Read> {
+    inner: R,
+    max_retries: u32,
+    retry_delay: Duration,
+}
+
+impl<R: Read> RetryReader<R> {
+    pub fn new(inner: R, max_retries: u32, retry_delay: Duration) -> Self {
+        Self {
+            inner,
+            max_retries,
+            retry_delay,
+        }
+    }
+}
+
+impl<R: Read> Read for RetryReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut retries = 0;
+        let mut delay = self.retry_delay;
+        loop {
+            match self.inner.read(buf) {
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) if retries < self.max_retries => {
+                    eprintln!("read error, retrying in {delay:?}: {e}");
+                    sleep(delay);
+                    retries += 1;
+                    delay = delay.saturating_mul(2);
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fails `failures` times before every successful read of one byte.
+    struct FlakyReader {
+        data: &'static [u8],
+        failures: u32,
+        failures_left: u32,
+    }
+
+    impl Read for FlakyReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if 0 < self.failures_left {
+                self.failures_left -= 1;
+                return Err(io::Error::new(io::ErrorKind::TimedOut, "timed out"));
+            }
+            self.failures_left = self.failures;
+            let len = self.data.len().min(buf.len()).min(1);
+            buf[..len].copy_from_slice(&self.data[..len]);
+            self.data = &self.data[len..];
+            Ok(len)
+        }
+    }
+
+    fn flaky_reader(failures: u32) -> FlakyReader {
+        FlakyReader {
+            data: b"abc",
+            failures,
+            failures_left: failures,
+        }
+    }
+
+    #[test]
+    fn test_retry_reader() {
+        let mut output = String::new();
+        RetryReader::new(flaky_reader(2), 2, Duration::ZERO)
+            .read_to_string(&mut output)
+            .unwrap();
+        assert_eq!(output, "abc");
+
+        let error = RetryReader::new(flaky_reader(3), 2, Duration::ZERO)
+            .read_to_string(&mut String::new())
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+    }
+}
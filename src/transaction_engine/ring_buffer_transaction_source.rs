@@ -0,0 +1,76 @@
+use super::Transaction;
+
+/// Keeps only the last `N` pushed transactions, e.g. to recompute balances over a rolling window with
+/// `process_transactions_and_return_client_states(source.iter_recent().cloned())`. For a window per client, keep one
+/// source per client.
+///
+/// Disputes, resolves and chargebacks whose deposit fell out of the window are ignored when the window is processed.
+#[derive(Debug, Clone)]
+pub struct RingBufferTransactionSource<const N: usize> {
+    buffer: [Option<Transaction>; N],
+    /// Where the next transaction goes, which is also where the oldest one is once the buffer is full.
+    head: usize,
+}
+
+impl<const N: usize> RingBufferTransactionSource<N> {
+    pub fn new() -> Self {
+        Self {
+            buffer: std::array::from_fn(|_| None),
+            head: 0,
+        }
+    }
+
+    /// Overwrites the oldest transaction once `N` are held.
+    pub fn push(&mut self, tx: Transaction) {
+        if N == 0 {
+            return;
+        }
+        self.buffer[self.head] = Some(tx);
+        self.head = (self.head + 1) % N;
+    }
+
+    /// The held transactions, oldest first.
+    pub fn iter_recent(&self) -> impl Iterator<Item = &Transaction> {
+        let (newer, older) = self.buffer.split_at(self.head);
+        older.iter().chain(newer).flatten()
+    }
+}
+
+impl<const N: usize> Default for RingBufferTransactionSource<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::transaction_processing_logic::process_transactions_and_return_client_states;
+    use super::super::TransactionType;
+    use super::*;
+
+    #[test]
+    fn test_ring_buffer_transaction_source() {
+        let deposit = |tx: u32| Transaction {
+            client: 1.into(),
+            tx: tx.into(),
+            tx_type: TransactionType::Deposit(tx.into()),
+        };
+        let mut source = RingBufferTransactionSource::<3>::new();
+        source.push(deposit(1));
+        source.push(deposit(2));
+        assert_eq!(
+            source.iter_recent().collect::<Vec<_>>(),
+            [&deposit(1), &deposit(2)]
+        );
+
+        for tx in 3..=5 {
+            source.push(deposit(tx));
+        }
+        assert_eq!(
+            source.iter_recent().collect::<Vec<_>>(),
+            [&deposit(3), &deposit(4), &deposit(5)]
+        );
+        let clients = process_transactions_and_return_client_states(source.iter_recent().cloned());
+        assert_eq!(clients[&1.into()].available, 12.into());
+    }
+}
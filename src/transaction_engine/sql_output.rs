@@ -0,0 +1,112 @@
+use super::transaction_processing_logic::process_transactions_and_return_client_states_with_log;
+use super::{ApplicationResult, ProcessingError, ProcessingStats, Transaction};
+use std::io::Write;
+
+/// Processes the transactions and writes SQL for loading the results into a relational database such as PostgreSQL
+/// or MySQL: one `INSERT INTO {table_name} (client, available, held, total, locked)` per client, sorted by client id,
+/// then one `INSERT INTO {table_name}_transaction_log (type, client, tx, amount, ignore_reason)` per transaction. The
+/// tables must already exist. `table_name` must be a plain identifier of ASCII letters, digits and underscores, since
+/// identifiers can't be escaped portably; values are written as escaped literals.
+pub fn process_transactions_and_emit_sql(
+    transactions: impl IntoIterator<Item = Transaction>,
+    mut output: impl Write,
+    table_name: &str,
+) -> Result<ProcessingStats, ProcessingError> {
+    let is_identifier = table_name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && table_name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !is_identifier {
+        return Err(ProcessingError::InvalidTableName(table_name.to_owned()));
+    }
+
+    let (clients, log) = process_transactions_and_return_client_states_with_log(transactions);
+    let mut stats = ProcessingStats {
+        transactions_read: log.entries().len() as u64,
+        transactions_ignored: log.ignored_count() as u64,
+        ..Default::default()
+    };
+    stats.record_clients(&clients);
+
+    let mut clients: Vec<_> = clients.into_iter().collect();
+    clients.sort_unstable_by_key(|&(client_id, _)| client_id);
+    for (client_id, client_state) in clients {
+        writeln!(
+            output,
+            "INSERT INTO {table_name} (client, available, held, total, locked) VALUES ({client_id}, {}, {}, {}, {});",
+            client_state.available,
+            client_state.held,
+            client_state.available + client_state.held,
+            if client_state.locked { "TRUE" } else { "FALSE" },
+        )?;
+    }
+    for (transaction, result) in log.entries() {
+        let amount = match transaction.tx_type.amount() {
+            Some(amount) => amount.to_string(),
+            None => "NULL".to_owned(),
+        };
+        let ignore_reason = match result {
+            ApplicationResult::Applied => "NULL".to_owned(),
+            ApplicationResult::Ignored(reason) => string_literal(&reason.to_string()),
+        };
+        writeln!(
+            output,
+            "INSERT INTO {table_name}_transaction_log (type, client, tx, amount, ignore_reason) VALUES ({}, {}, {}, {amount}, {ignore_reason});",
+            string_literal(transaction.tx_type.name()),
+            transaction.client,
+            transaction.tx,
+        )?;
+    }
+    output.flush()?;
+    Ok(stats)
+}
+
+/// A standard SQL string literal: single quotes, with embedded ones doubled.
+fn string_literal(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{AmountType, TransactionType};
+    use super::*;
+
+    #[test]
+    fn test_emit_sql() {
+        let transactions = [
+            Transaction {
+                client: 1.into(),
+                tx: 1.into(),
+                tx_type: TransactionType::Deposit(AmountType::from_str_exact("1.5").unwrap()),
+            },
+            Transaction {
+                client: 2.into(),
+                tx: 1.into(),
+                tx_type: TransactionType::Dispute,
+            },
+        ];
+
+        let mut output = Vec::new();
+        let stats =
+            process_transactions_and_emit_sql(transactions.clone(), &mut output, "balances")
+                .unwrap();
+        assert_eq!(stats.transactions_read, 2);
+        assert_eq!(stats.transactions_ignored, 1);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "INSERT INTO balances (client, available, held, total, locked) VALUES (1, 1.5, 0, 1.5, FALSE);\n\
+             INSERT INTO balances (client, available, held, total, locked) VALUES (2, 0, 0, 0, FALSE);\n\
+             INSERT INTO balances_transaction_log (type, client, tx, amount, ignore_reason) VALUES ('deposit', 1, 1, 1.5, NULL);\n\
+             INSERT INTO balances_transaction_log (type, client, tx, amount, ignore_reason) VALUES ('dispute', 2, 1, NULL, 'the client who files the dispute is different from the one who made the deposit');\n"
+        );
+
+        assert!(matches!(
+            process_transactions_and_emit_sql(transactions, Vec::new(), "balances; DROP TABLE x"),
+            Err(ProcessingError::InvalidTableName(_))
+        ));
+        assert_eq!(string_literal("can't"), "'can''t'");
+    }
+}
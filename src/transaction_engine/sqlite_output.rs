@@ -0,0 +1,136 @@
+use super::transaction_processing_logic::process_transactions_and_return_client_states_with_log;
+use super::{ApplicationResult, ProcessingError, ProcessingStats, Transaction};
+use rusqlite::{params, Connection};
+use std::path::Path;
+
+const CREATE_TABLES: &str = "
+    CREATE TABLE IF NOT EXISTS client_states (
+        client INTEGER PRIMARY KEY,
+        available TEXT NOT NULL,
+        held TEXT NOT NULL,
+        total TEXT NOT NULL,
+        locked BOOLEAN NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS transaction_log (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        type TEXT NOT NULL,
+        client INTEGER NOT NULL,
+        tx INTEGER NOT NULL,
+        amount TEXT,
+        ignore_reason TEXT
+    );";
+
+/// Processes the transactions and writes the results to the SQLite database at `db_path`, creating it if needed: the
+/// final client states are upserted into `client_states`, with the columns of the CSV output, and every transaction is
+/// appended to `transaction_log` along with the reason it was ignored, if it was. Amounts are stored as text to keep
+/// their exact precision. Everything is written in one database transaction.
+pub fn process_transactions_to_sqlite(
+    transactions: impl IntoIterator<Item = Transaction>,
+    db_path: &Path,
+) -> Result<ProcessingStats, ProcessingError> {
+    let mut connection = Connection::open(db_path)?;
+    connection.execute_batch(CREATE_TABLES)?;
+    let db_transaction = connection.transaction()?;
+
+    let (clients, log) = process_transactions_and_return_client_states_with_log(transactions);
+    let mut stats = ProcessingStats {
+        transactions_read: log.entries().len() as u64,
+        transactions_ignored: log.ignored_count() as u64,
+        ..Default::default()
+    };
+    {
+        let mut insert_log_entry = db_transaction.prepare(
+            "INSERT INTO transaction_log (type, client, tx, amount, ignore_reason)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+        )?;
+        for (transaction, result) in log.entries() {
+            let ignore_reason = match result {
+                ApplicationResult::Applied => None,
+                ApplicationResult::Ignored(reason) => Some(reason.to_string()),
+            };
+            insert_log_entry.execute(params![
+                transaction.tx_type.name(),
+                u16::from(transaction.client),
+                u32::from(transaction.tx),
+                transaction
+                    .tx_type
+                    .amount()
+                    .map(|amount| amount.to_string()),
+                ignore_reason,
+            ])?;
+        }
+
+        let mut upsert_client_state = db_transaction.prepare(
+            "INSERT OR REPLACE INTO client_states (client, available, held, total, locked)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+        )?;
+        for (client_id, client_state) in &clients {
+            upsert_client_state.execute(params![
+                u16::from(*client_id),
+                client_state.available.to_string(),
+                client_state.held.to_string(),
+                (client_state.available + client_state.held).to_string(),
+                client_state.locked,
+            ])?;
+        }
+    }
+    db_transaction.commit()?;
+
+    stats.record_clients(&clients);
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{AmountType, TransactionType};
+    use super::*;
+
+    #[test]
+    fn test_process_transactions_to_sqlite() {
+        let db_path =
+            std::env::temp_dir().join(format!("transaction_engine_{}.sqlite", std::process::id()));
+        let _ = std::fs::remove_file(&db_path);
+
+        let stats = process_transactions_to_sqlite(
+            [
+                Transaction {
+                    client: 1.into(),
+                    tx: 1.into(),
+                    tx_type: TransactionType::Deposit(AmountType::from_str_exact("1.5").unwrap()),
+                },
+                Transaction {
+                    client: 1.into(),
+                    tx: 2.into(),
+                    tx_type: TransactionType::Withdrawal(AmountType::from_str_exact("2").unwrap()),
+                },
+            ],
+            &db_path,
+        )
+        .unwrap();
+        assert_eq!(stats.transactions_read, 2);
+        assert_eq!(stats.transactions_ignored, 1);
+
+        let connection = Connection::open(&db_path).unwrap();
+        let client_state: (u16, String, bool) = connection
+            .query_row(
+                "SELECT client, available, locked FROM client_states",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!(client_state, (1, "1.5".to_owned(), false));
+        let ignore_reasons: Vec<Option<String>> = connection
+            .prepare("SELECT ignore_reason FROM transaction_log ORDER BY id")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            ignore_reasons,
+            vec![None, Some("not enough available funds".to_owned())]
+        );
+
+        std::fs::remove_file(&db_path).unwrap();
+    }
+}
@@ -0,0 +1,503 @@
+use super::output_format::strip_schema_version;
+#[cfg(test)]
+use super::transaction_processing_logic::process_transactions_and_return_client_states;
+use super::transaction_processing_logic::{CHARGEBACK, DEPOSIT, DISPUTE, RESOLVE, WITHDRAWAL};
+use super::{
+    process_csv_transactions_and_return_csv_client_states, AmountType, ClientId, ClientState,
+    InputFormat, OutputCsvRecord, Transaction, TransactionId, TransactionType::*,
+};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+/// A reproducible mix of deposits, withdrawals, disputes, resolves and chargebacks. Disputes only reference earlier
+/// deposits of the same client, and resolves and chargebacks only reference deposits under dispute. Withdrawals and
+/// disputes may still be ignored for lack of funds, as in real traffic.
+pub fn random_transactions(
+    seed: u64,
+    num_clients: u16,
+    num_transactions: usize,
+) -> Vec<Transaction> {
+    assert!(0 < num_clients, "at least one client is required");
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut undisputed_deposits = HashMap::<ClientId, Vec<TransactionId>>::new();
+    let mut disputed_deposits = HashMap::<ClientId, Vec<TransactionId>>::new();
+    let mut next_tx = 1u32;
+    let mut transactions = Vec::with_capacity(num_transactions);
+
+    while transactions.len() < num_transactions {
+        let client = ClientId::from(rng.gen_range(1..=num_clients));
+        let undisputed = undisputed_deposits.entry(client).or_default();
+        let disputed = disputed_deposits.entry(client).or_default();
+        let amount = AmountType::new(rng.gen_range(1..=10_000_000), 4);
+
+        let (tx, tx_type) = match rng.gen_range(0..100) {
+            0..=49 => {
+                undisputed.push(next_tx.into());
+                (next_tx.into(), Deposit(amount))
+            }
+            50..=74 => (next_tx.into(), Withdrawal(amount)),
+            75..=86 if !undisputed.is_empty() => {
+                let tx = undisputed.swap_remove(rng.gen_range(0..undisputed.len()));
+                disputed.push(tx);
+                (tx, Dispute)
+            }
+            87..=94 if !disputed.is_empty() => {
+                let tx = disputed.swap_remove(rng.gen_range(0..disputed.len()));
+                undisputed.push(tx);
+                (tx, Resolve)
+            }
+            95..=99 if !disputed.is_empty() => (
+                disputed.swap_remove(rng.gen_range(0..disputed.len())),
+                Chargeback,
+            ),
+            _ => continue,
+        };
+        if matches!(tx_type, Deposit(_) | Withdrawal(_)) {
+            next_tx += 1;
+        }
+        transactions.push(Transaction {
+            client,
+            tx,
+            tx_type,
+        });
+    }
+
+    transactions
+}
+
+/// A reproducible input CSV of `transactions_per_client` transactions for each of `num_clients` clients, interleaved,
+/// for documentation examples, load tests and smoke tests. Mostly deposits, with some withdrawals, some disputes that
+/// are resolved, and a few chargebacks, each the last transaction of its client. None of them is ignored: withdrawals
+/// and disputes never exceed the available funds.
+pub fn generate_sample_csv(num_clients: u16, transactions_per_client: u32, seed: u64) -> String {
+    #[derive(Default)]
+    struct SampleClient {
+        available: AmountType,
+        undisputed: Vec<(TransactionId, AmountType)>,
+        disputed: Vec<(TransactionId, AmountType)>,
+    }
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut clients: Vec<SampleClient> = (0..num_clients).map(|_| Default::default()).collect();
+    let mut next_tx = 1u32;
+    let mut csv = String::from("type,client,tx,amount\n");
+
+    for round in 0..transactions_per_client {
+        let is_last = round + 1 == transactions_per_client;
+        for (client_id, client) in (1..=num_clients).zip(&mut clients) {
+            let roll = rng.gen_range(0..100);
+            let disputable = client
+                .undisputed
+                .iter()
+                .position(|&(_, amount)| amount <= client.available)
+                .filter(|_| (75..87).contains(&roll));
+            let (record_type, tx, amount) = if is_last && roll < 20 && !client.disputed.is_empty() {
+                (CHARGEBACK, client.disputed.swap_remove(0).0, None)
+            } else if (60..75).contains(&roll) && client.available > AmountType::ZERO {
+                let amount = AmountType::new(rng.gen_range(1..=10_000), 4).min(client.available);
+                client.available -= amount;
+                next_tx += 1;
+                (WITHDRAWAL, (next_tx - 1).into(), Some(amount))
+            } else if let Some(index) = disputable {
+                let (tx, amount) = client.undisputed.swap_remove(index);
+                client.available -= amount;
+                client.disputed.push((tx, amount));
+                (DISPUTE, tx, None)
+            } else if 87 <= roll && !client.disputed.is_empty() {
+                let (tx, amount) = client.disputed.swap_remove(0);
+                client.available += amount;
+                client.undisputed.push((tx, amount));
+                (RESOLVE, tx, None)
+            } else {
+                let amount = AmountType::new(rng.gen_range(1..=1_000_000), 4);
+                client.available += amount;
+                client.undisputed.push((next_tx.into(), amount));
+                next_tx += 1;
+                (DEPOSIT, (next_tx - 1).into(), Some(amount))
+            };
+            let amount = amount.map(|amount| amount.to_string()).unwrap_or_default();
+            csv += &format!("{record_type},{client_id},{tx},{amount}\n");
+        }
+    }
+
+    csv
+}
+
+/// The differences `assert_csv_output_matches` found, by client id.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssertionError {
+    /// The fields that differ for clients in both outputs, e.g. `client 1: held is 0, expected 2`.
+    pub mismatched: Vec<String>,
+    /// The output lines of clients that only the actual output has.
+    pub extra: Vec<String>,
+    /// The expected lines of clients that the actual output lacks.
+    pub missing: Vec<String>,
+}
+
+impl Display for AssertionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "client states differ:")?;
+        for line in &self.mismatched {
+            writeln!(f, "~ {line}")?;
+        }
+        for line in &self.extra {
+            writeln!(f, "+ {line}")?;
+        }
+        for line in &self.missing {
+            writeln!(f, "- {line}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for AssertionError {}
+
+/// Processes `input_csv` and compares the client states with `expected_output_csv` field by field, regardless of the
+/// order of the rows and of how the amounts are scaled. The `total` column of `expected_output_csv` is ignored, since
+/// it is derived from the other two. Panics if either CSV is malformed.
+pub fn assert_csv_output_matches(
+    input_csv: &str,
+    expected_output_csv: &str,
+) -> Result<(), AssertionError> {
+    let actual_output_csv = process_csv_transactions_and_return_csv_client_states(
+        input_csv.as_bytes(),
+        InputFormat::Csv,
+    )
+    .expect("the input should be valid CSV");
+    let actual = parse_client_states_csv(&actual_output_csv);
+    let expected = parse_client_states_csv(expected_output_csv);
+
+    let output_line = |client_id: ClientId, client_state: &ClientState| {
+        format!(
+            "{client_id},{},{},{},{}",
+            client_state.available,
+            client_state.held,
+            client_state.available + client_state.held,
+            client_state.locked
+        )
+    };
+    let mut error = AssertionError {
+        mismatched: Vec::new(),
+        extra: Vec::new(),
+        missing: Vec::new(),
+    };
+    for client_id in sorted_client_ids(&actual, &expected) {
+        match (actual.get(&client_id), expected.get(&client_id)) {
+            (Some(actual), None) => error.extra.push(output_line(client_id, actual)),
+            (None, Some(expected)) => error.missing.push(output_line(client_id, expected)),
+            (Some(actual), Some(expected)) => error
+                .mismatched
+                .extend(diff_client_states(client_id, actual, expected)),
+            (None, None) => unreachable!("the ids come from either map"),
+        }
+    }
+
+    if error.mismatched.is_empty() && error.extra.is_empty() && error.missing.is_empty() {
+        Ok(())
+    } else {
+        Err(error)
+    }
+}
+
+/// The ids of the clients in either map, sorted.
+fn sorted_client_ids(
+    actual: &HashMap<ClientId, ClientState>,
+    expected: &HashMap<ClientId, ClientState>,
+) -> Vec<ClientId> {
+    let mut sorted_ids: Vec<_> = actual.keys().chain(expected.keys()).copied().collect();
+    sorted_ids.sort_unstable();
+    sorted_ids.dedup();
+    sorted_ids
+}
+
+/// One line for each field of `client_id`'s state that differs, shared by the assertions on client states. The fields
+/// behind features aren't listed, and differences in them are reported as a whole, so that there is a line whenever
+/// the states differ.
+fn diff_client_states(
+    client_id: ClientId,
+    actual: &ClientState,
+    expected: &ClientState,
+) -> Vec<String> {
+    let fields = [
+        (
+            "available",
+            actual.available == expected.available,
+            actual.available.to_string(),
+            expected.available.to_string(),
+        ),
+        (
+            "held",
+            actual.held == expected.held,
+            actual.held.to_string(),
+            expected.held.to_string(),
+        ),
+        (
+            "locked",
+            actual.locked == expected.locked,
+            actual.locked.to_string(),
+            expected.locked.to_string(),
+        ),
+        (
+            "pending_locked_transactions",
+            actual.pending_locked_transactions == expected.pending_locked_transactions,
+            format!("{:?}", actual.pending_locked_transactions),
+            format!("{:?}", expected.pending_locked_transactions),
+        ),
+    ];
+    let mut diff: Vec<_> = fields
+        .into_iter()
+        .filter(|&(_, equal, ..)| !equal)
+        .map(|(field, _, actual, expected)| {
+            format!("{client_id:#}: {field} is {actual}, expected {expected}")
+        })
+        .collect();
+    if diff.is_empty() && actual != expected {
+        diff.push(format!(
+            "{client_id:#}: other fields differ: {actual:?}, expected {expected:?}"
+        ));
+    }
+    diff
+}
+
+fn parse_client_states_csv(csv: &str) -> HashMap<ClientId, ClientState> {
+    let (_version, csv) =
+        strip_schema_version(csv).expect("the schema version should be supported");
+    csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(csv.as_bytes())
+        .into_deserialize()
+        .map(|record| {
+            let record: OutputCsvRecord = record.expect("the client states should be valid CSV");
+            (
+                record.client,
+                ClientState {
+                    available: record.available,
+                    held: record.held,
+                    locked: record.locked,
+                    ..Default::default()
+                },
+            )
+        })
+        .collect()
+}
+
+/// Panics with a readable diff if the client states differ: the clients only in `actual`, the ones only in `expected`,
+/// and the fields that differ for the clients in both.
+#[cfg(test)]
+pub(crate) fn assert_client_state_equals(
+    actual: &HashMap<ClientId, super::ClientState>,
+    expected: &HashMap<ClientId, super::ClientState>,
+) {
+    let mut diff = Vec::new();
+    for client_id in sorted_client_ids(actual, expected) {
+        match (actual.get(&client_id), expected.get(&client_id)) {
+            (Some(_), None) => diff.push(format!("{client_id:#}: only in actual")),
+            (None, Some(_)) => diff.push(format!("{client_id:#}: only in expected")),
+            (Some(actual), Some(expected)) => {
+                diff.extend(diff_client_states(client_id, actual, expected))
+            }
+            (None, None) => unreachable!("the ids come from either map"),
+        }
+    }
+
+    assert!(
+        diff.is_empty(),
+        "client states differ:\n{}",
+        diff.join("\n")
+    );
+}
+
+/// Panics if processing `transactions` twice gives different client states.
+#[cfg(test)]
+pub(crate) fn assert_idempotent(transactions: Vec<Transaction>) {
+    assert_client_state_equals(
+        &process_transactions_and_return_client_states(transactions.clone()),
+        &process_transactions_and_return_client_states(transactions),
+    );
+}
+
+/// Panics if any ordering of `transactions` gives different client states than the given one. Order generally
+/// matters, so this documents the cases where it doesn't, e.g. the transactions of unrelated clients. All orderings are
+/// tried, hence the limit of 8 transactions.
+#[cfg(test)]
+pub(crate) fn assert_order_independent(mut transactions: Vec<Transaction>) {
+    fn for_each_permutation(
+        items: &mut [Transaction],
+        len: usize,
+        f: &mut impl FnMut(&[Transaction]),
+    ) {
+        if len <= 1 {
+            return f(items);
+        }
+        // Heap's algorithm.
+        for i in 0..len - 1 {
+            for_each_permutation(items, len - 1, f);
+            items.swap(if len.is_multiple_of(2) { i } else { 0 }, len - 1);
+        }
+        for_each_permutation(items, len - 1, f);
+    }
+
+    assert!(
+        transactions.len() <= 8,
+        "too many transactions to try all orderings"
+    );
+    let expected = process_transactions_and_return_client_states(transactions.clone());
+    let len = transactions.len();
+    for_each_permutation(&mut transactions, len, &mut |permutation| {
+        assert_client_state_equals(
+            &process_transactions_and_return_client_states(permutation.to_vec()),
+            &expected,
+        );
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{process_transactions_dry_run, IgnoreReason};
+    use super::*;
+
+    #[test]
+    fn test_assert_csv_output_matches() {
+        let input = "type,client,tx,amount\n\
+                     deposit,1,1,2\n\
+                     deposit,2,2,1.5\n\
+                     dispute,2,2,\n";
+        assert_csv_output_matches(
+            input,
+            "client,available,held,total,locked\n\
+             2, 0.0, 1.50, 1.5, false\n\
+             1, 2, 0, 2, false\n",
+        )
+        .unwrap();
+
+        let error = assert_csv_output_matches(
+            input,
+            "client,available,held,total,locked\n\
+             2,1.5,0,1.5,false\n\
+             3,1,0,1,false\n",
+        )
+        .unwrap_err();
+        assert_eq!(
+            error,
+            AssertionError {
+                mismatched: vec![
+                    "client 2: available is 0.0, expected 1.5".to_owned(),
+                    "client 2: held is 1.5, expected 0".to_owned(),
+                ],
+                extra: vec!["1,2,0,2,false".to_owned()],
+                missing: vec!["3,1,0,1,false".to_owned()],
+            }
+        );
+    }
+
+    #[test]
+    fn test_assert_client_state_equals_diff() {
+        let client_state = |available| super::super::ClientState {
+            available,
+            ..Default::default()
+        };
+        let actual = HashMap::from([
+            (1.into(), client_state(AmountType::ONE)),
+            (2.into(), client_state(AmountType::ONE)),
+        ]);
+        let expected = HashMap::from([
+            (1.into(), client_state(AmountType::TWO)),
+            (3.into(), client_state(AmountType::ONE)),
+        ]);
+        assert_client_state_equals(&actual, &actual);
+
+        let message = *std::panic::catch_unwind(|| assert_client_state_equals(&actual, &expected))
+            .unwrap_err()
+            .downcast::<String>()
+            .unwrap();
+        assert_eq!(
+            message,
+            "client states differ:\n\
+             client 1: available is 1, expected 2\n\
+             client 2: only in actual\n\
+             client 3: only in expected"
+        );
+    }
+
+    #[cfg(feature = "detailed-stats")]
+    #[test]
+    #[should_panic(expected = "client 1: other fields differ")]
+    fn test_assert_client_state_equals_other_fields() {
+        let client_state = |deposit_count| super::super::ClientState {
+            deposit_count,
+            ..Default::default()
+        };
+        assert_client_state_equals(
+            &HashMap::from([(1.into(), client_state(1))]),
+            &HashMap::from([(1.into(), client_state(2))]),
+        );
+    }
+
+    #[test]
+    fn test_random_transactions() {
+        let transactions = random_transactions(42, 10, 1000);
+
+        assert_eq!(transactions.len(), 1000);
+        assert_eq!(transactions, random_transactions(42, 10, 1000));
+        assert!(process_transactions_dry_run(transactions)
+            .iter()
+            .all(|(_, reason)| !matches!(
+                reason,
+                IgnoreReason::NoDepositFound
+                    | IgnoreReason::NoDisputeFound
+                    | IgnoreReason::DisputeClientMismatch
+                    | IgnoreReason::ResolveClientMismatch
+                    | IgnoreReason::ChargebackClientMismatch
+            )));
+    }
+
+    #[test]
+    fn test_generate_sample_csv() {
+        let csv = generate_sample_csv(20, 50, 7);
+        assert_eq!(csv, generate_sample_csv(20, 50, 7));
+        assert_eq!(csv.lines().count(), 1 + 20 * 50);
+        for record_type in ["deposit", "withdrawal", "dispute", "resolve", "chargeback"] {
+            assert!(
+                csv.lines().any(|line| line.starts_with(record_type)),
+                "no {record_type} in the sample"
+            );
+        }
+
+        let transactions: Vec<Transaction> = csv::Reader::from_reader(csv.as_bytes())
+            .deserialize::<super::super::InputCsvRecord>()
+            .map(|record| Transaction::try_from(record.unwrap()).unwrap())
+            .collect();
+        assert_eq!(process_transactions_dry_run(transactions), []);
+    }
+
+    #[test]
+    fn test_assert_idempotent_and_order_independent() {
+        let transactions: Vec<_> = (1..=4u16)
+            .map(|client| Transaction {
+                client: client.into(),
+                tx: u32::from(client).into(),
+                tx_type: Deposit(client.into()),
+            })
+            .collect();
+        assert_idempotent(transactions.clone());
+        assert_order_independent(transactions);
+
+        let dependent = vec![
+            Transaction {
+                client: 1.into(),
+                tx: 1.into(),
+                tx_type: Deposit(1.into()),
+            },
+            Transaction {
+                client: 1.into(),
+                tx: 2.into(),
+                tx_type: Withdrawal(1.into()),
+            },
+        ];
+        assert!(std::panic::catch_unwind(|| assert_order_independent(dependent)).is_err());
+    }
+}
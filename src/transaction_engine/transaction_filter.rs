@@ -0,0 +1,182 @@
+use super::transaction_processing_logic::{
+    CHARGEBACK, CORRECTION, DEPOSIT, DISPUTE, RESOLVE, WITHDRAWAL,
+};
+use super::{AmountType, ClientId, ProcessingError, Transaction, TransactionType};
+use std::collections::HashSet;
+use std::mem::discriminant;
+use std::str::FromStr;
+
+/// Decides which transactions are processed, see `process_transactions_filtered`. The implementations below compose
+/// into arbitrary predicates, e.g. `And(vec![Box::new(ByClientId(..)), Box::new(Not(Box::new(ByTransactionType(..))))])`.
+pub trait TransactionFilter: Send + Sync {
+    fn matches(&self, tx: &Transaction) -> bool;
+}
+
+pub struct ByClientId(pub HashSet<ClientId>);
+
+impl TransactionFilter for ByClientId {
+    fn matches(&self, tx: &Transaction) -> bool {
+        self.0.contains(&tx.client)
+    }
+}
+
+/// Matches on the kind of transaction only; the amount and force flag of the given type are disregarded.
+pub struct ByTransactionType(pub TransactionType);
+
+impl TransactionFilter for ByTransactionType {
+    fn matches(&self, tx: &Transaction) -> bool {
+        discriminant(&self.0) == discriminant(&tx.tx_type)
+    }
+}
+
+/// Matches deposits, withdrawals and corrections with an amount within `min..=max`. Disputes, resolves and chargebacks
+/// carry no amount and never match.
+pub struct ByAmountRange {
+    pub min: AmountType,
+    pub max: AmountType,
+}
+
+impl TransactionFilter for ByAmountRange {
+    fn matches(&self, tx: &Transaction) -> bool {
+        match tx.tx_type {
+            TransactionType::Deposit(amount)
+            | TransactionType::Withdrawal(amount)
+            | TransactionType::Correction(amount, _) => (self.min..=self.max).contains(&amount),
+            _ => false,
+        }
+    }
+}
+
+pub struct Not(pub Box<dyn TransactionFilter>);
+
+impl TransactionFilter for Not {
+    fn matches(&self, tx: &Transaction) -> bool {
+        !self.0.matches(tx)
+    }
+}
+
+/// Matches when all of the filters match, including when there are none.
+pub struct And(pub Vec<Box<dyn TransactionFilter>>);
+
+impl TransactionFilter for And {
+    fn matches(&self, tx: &Transaction) -> bool {
+        self.0.iter().all(|filter| filter.matches(tx))
+    }
+}
+
+/// Matches when any of the filters matches, so never when there are none.
+pub struct Or(pub Vec<Box<dyn TransactionFilter>>);
+
+impl TransactionFilter for Or {
+    fn matches(&self, tx: &Transaction) -> bool {
+        self.0.iter().any(|filter| filter.matches(tx))
+    }
+}
+
+/// A transaction type without its payload, parsed from the record type names of the input, e.g. `"deposit"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TransactionTypeFilter {
+    Deposit,
+    Withdrawal,
+    Dispute,
+    Resolve,
+    Chargeback,
+    Correction,
+}
+
+impl TransactionTypeFilter {
+    /// Parses a comma-separated list such as `"dispute,chargeback"`.
+    pub fn parse_list(s: &str) -> Result<Vec<Self>, ProcessingError> {
+        s.split(',').map(|name| name.trim().parse()).collect()
+    }
+}
+
+impl FromStr for TransactionTypeFilter {
+    type Err = ProcessingError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            DEPOSIT => Ok(TransactionTypeFilter::Deposit),
+            WITHDRAWAL => Ok(TransactionTypeFilter::Withdrawal),
+            DISPUTE => Ok(TransactionTypeFilter::Dispute),
+            RESOLVE => Ok(TransactionTypeFilter::Resolve),
+            CHARGEBACK => Ok(TransactionTypeFilter::Chargeback),
+            CORRECTION => Ok(TransactionTypeFilter::Correction),
+            _ => Err(ProcessingError::UnknownTransactionType(s.to_owned())),
+        }
+    }
+}
+
+impl TransactionFilter for TransactionTypeFilter {
+    fn matches(&self, tx: &Transaction) -> bool {
+        matches!(
+            (self, &tx.tx_type),
+            (TransactionTypeFilter::Deposit, TransactionType::Deposit(_))
+                | (
+                    TransactionTypeFilter::Withdrawal,
+                    TransactionType::Withdrawal(_)
+                )
+                | (TransactionTypeFilter::Dispute, TransactionType::Dispute)
+                | (TransactionTypeFilter::Resolve, TransactionType::Resolve)
+                | (
+                    TransactionTypeFilter::Chargeback,
+                    TransactionType::Chargeback
+                )
+                | (
+                    TransactionTypeFilter::Correction,
+                    TransactionType::Correction(..)
+                )
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_combinators() {
+        let transaction = |client: u16, tx_type| Transaction {
+            client: client.into(),
+            tx: 1.into(),
+            tx_type,
+        };
+        let deposit = transaction(
+            1,
+            TransactionType::Deposit(AmountType::from_str_exact("5").unwrap()),
+        );
+        let withdrawal = transaction(
+            1,
+            TransactionType::Withdrawal(AmountType::from_str_exact("50").unwrap()),
+        );
+        let dispute = transaction(2, TransactionType::Dispute);
+
+        // Client 1's transactions other than small deposits, or any dispute.
+        let filter = Or(vec![
+            Box::new(And(vec![
+                Box::new(ByClientId(HashSet::from([1.into()]))),
+                Box::new(Not(Box::new(And(vec![
+                    Box::new(ByTransactionType(TransactionType::Deposit(
+                        AmountType::ZERO,
+                    ))),
+                    Box::new(ByAmountRange {
+                        min: AmountType::ZERO,
+                        max: AmountType::TEN,
+                    }),
+                ])))),
+            ])),
+            Box::new(ByTransactionType(TransactionType::Dispute)),
+        ]);
+
+        assert!(!filter.matches(&deposit));
+        assert!(filter.matches(&withdrawal));
+        assert!(filter.matches(&dispute));
+        assert!(!ByAmountRange {
+            min: AmountType::ZERO,
+            max: AmountType::MAX,
+        }
+        .matches(&dispute));
+        assert!(!Or(vec![]).matches(&deposit));
+        assert!(And(vec![]).matches(&deposit));
+    }
+}
@@ -0,0 +1,27 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display, Formatter};
+
+/// An identifier, not a quantity: no arithmetic is provided, so ids can't be added or multiplied by accident.
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct TransactionId(u32);
+
+impl Display for TransactionId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl From<u32> for TransactionId {
+    fn from(id: u32) -> Self {
+        TransactionId(id)
+    }
+}
+
+impl From<TransactionId> for u32 {
+    fn from(id: TransactionId) -> Self {
+        id.0
+    }
+}
@@ -0,0 +1,306 @@
+use super::input_format::{assert_required_columns, parse_csv_line, validate_csv_header};
+use super::transaction_processing_logic::{
+    apply_transaction, process_transactions_and_return_client_states,
+};
+use super::{
+    AmountType, ClientId, ClientState, DepositLog, IgnoreReason, ProcessingError, Transaction,
+    TransactionId, TransactionType,
+};
+use csv::{StringRecord, Writer};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApplicationResult {
+    Applied,
+    Ignored(IgnoreReason),
+}
+
+/// The full history of a processing run: every transaction in arrival order with what became of it, see
+/// `process_transactions_and_return_client_states_with_log`. Unlike the client states, it grows with the number of
+/// transactions.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TransactionLog {
+    entries: Vec<(Transaction, ApplicationResult)>,
+}
+
+#[derive(Serialize)]
+struct TransactionLogCsvRecord {
+    #[serde(rename = "type")]
+    record_type: &'static str,
+    client: ClientId,
+    tx: TransactionId,
+    #[serde(with = "rust_decimal::serde::str_option")]
+    amount: Option<AmountType>,
+    /// Empty if the transaction was applied.
+    ignore_reason: Option<String>,
+}
+
+impl TransactionLog {
+    pub(super) fn push(&mut self, transaction: Transaction, result: ApplicationResult) {
+        self.entries.push((transaction, result));
+    }
+
+    pub fn entries(&self) -> &[(Transaction, ApplicationResult)] {
+        &self.entries
+    }
+
+    pub fn applied_count(&self) -> usize {
+        self.entries
+            .iter()
+            .filter(|(_, result)| *result == ApplicationResult::Applied)
+            .count()
+    }
+
+    pub fn ignored_count(&self) -> usize {
+        self.entries.len() - self.applied_count()
+    }
+
+    /// The entries for which `predicate` holds, e.g. the ignored ones of a single client.
+    pub fn filter(
+        &self,
+        mut predicate: impl FnMut(&Transaction, &ApplicationResult) -> bool,
+    ) -> TransactionLog {
+        TransactionLog {
+            entries: self
+                .entries
+                .iter()
+                .filter(|(transaction, result)| predicate(transaction, result))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Processes the logged transactions again from scratch; for a complete log, this reproduces the client states.
+    pub fn replay(&self) -> HashMap<ClientId, ClientState> {
+        process_transactions_and_return_client_states(
+            self.entries
+                .iter()
+                .map(|(transaction, _)| transaction.clone()),
+        )
+    }
+
+    /// The entries as CSV with the input columns `type`, `client`, `tx` and `amount`, plus `ignore_reason`, which is
+    /// empty for applied transactions.
+    pub fn to_csv(&self) -> Result<String, ProcessingError> {
+        let mut writer = Writer::from_writer(Vec::new());
+        for record in self.records() {
+            writer.serialize(record)?;
+        }
+        let csv_output = writer.into_inner().map_err(|e| e.into_error())?;
+        Ok(String::from_utf8(csv_output).expect("the log is written as UTF-8"))
+    }
+
+    /// The entries as a JSON array of objects with the same keys as the columns of `to_csv`.
+    pub fn to_json(&self) -> Result<String, ProcessingError> {
+        Ok(serde_json::to_string(&self.records().collect::<Vec<_>>())?)
+    }
+
+    fn records(&self) -> impl Iterator<Item = TransactionLogCsvRecord> + '_ {
+        self.entries
+            .iter()
+            .map(|(transaction, result)| TransactionLogCsvRecord {
+                record_type: transaction.tx_type.name(),
+                client: transaction.client,
+                tx: transaction.tx,
+                amount: transaction.tx_type.amount(),
+                ignore_reason: match result {
+                    ApplicationResult::Applied => None,
+                    ApplicationResult::Ignored(reason) => Some(reason.to_string()),
+                },
+            })
+    }
+}
+
+/// Builds a `TransactionLog` from CSV input written to it in chunks of any size, e.g. straight from a socket, without
+/// holding the whole input in memory. Each complete line is parsed and applied as soon as its newline arrives, the
+/// first one being the header; quoted fields spanning lines aren't supported. As when reading, malformed rows are
+/// logged to stderr and skipped, while a header that lacks a required column fails the write.
+#[derive(Debug, Default)]
+pub struct TransactionLogWriter {
+    partial_line: Vec<u8>,
+    headers: Option<StringRecord>,
+    clients: HashMap<ClientId, ClientState>,
+    deposit_log: DepositLog,
+    log: TransactionLog,
+}
+
+impl TransactionLogWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The transactions of the complete lines written so far.
+    pub fn log(&self) -> &TransactionLog {
+        &self.log
+    }
+
+    /// Parses what is left after the last newline as a final line, and returns the log.
+    pub fn into_log(mut self) -> io::Result<TransactionLog> {
+        let last_line = std::mem::take(&mut self.partial_line);
+        self.parse_line(&last_line)?;
+        Ok(self.log)
+    }
+
+    fn parse_line(&mut self, line: &[u8]) -> io::Result<()> {
+        let Some(headers) = &self.headers else {
+            let headers = validate_csv_header(&mut &line[..]).map_err(io::Error::other)?;
+            // Leading blank lines are skipped, like empty input.
+            if !headers.is_empty() {
+                assert_required_columns(&headers).map_err(io::Error::other)?;
+                self.headers = Some(StringRecord::from(headers));
+            }
+            return Ok(());
+        };
+
+        if let Some(transaction) = parse_csv_line(line, headers) {
+            let result =
+                match apply_transaction(&mut self.clients, &mut self.deposit_log, &transaction) {
+                    Ok(()) => ApplicationResult::Applied,
+                    Err(reason) => ApplicationResult::Ignored(reason),
+                };
+            self.log.push(transaction, result);
+        }
+        Ok(())
+    }
+}
+
+impl Write for TransactionLogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.partial_line.extend_from_slice(buf);
+        while let Some(newline) = self.partial_line.iter().position(|&byte| byte == b'\n') {
+            let line: Vec<u8> = self.partial_line.drain(..=newline).collect();
+            self.parse_line(&line)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Replays `original_log` and returns the client and amount of every chargeback that applies, in log order: what the
+/// clients would get back if all chargebacks were reversed, e.g. for a fraud review.
+pub fn reprocess_disputed_transactions(
+    original_log: &TransactionLog,
+) -> Vec<(ClientId, AmountType)> {
+    let mut clients = HashMap::new();
+    let mut deposit_log = DepositLog::new();
+    let mut charged_back = Vec::new();
+    for (transaction, _) in &original_log.entries {
+        let held = |clients: &HashMap<ClientId, ClientState>| {
+            clients
+                .get(&transaction.client)
+                .map_or(AmountType::ZERO, |client_state| client_state.held)
+        };
+        let held_before = held(&clients);
+        let result = apply_transaction(&mut clients, &mut deposit_log, transaction);
+        if let (TransactionType::Chargeback, Ok(())) = (&transaction.tx_type, result) {
+            charged_back.push((transaction.client, held_before - held(&clients)));
+        }
+    }
+    charged_back
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::transaction_processing_logic::process_transactions_and_return_client_states_with_log;
+    use super::super::TransactionType;
+    use super::*;
+
+    #[test]
+    fn test_reprocess_disputed_transactions() {
+        let transaction = |client: u16, tx: u32, tx_type| Transaction {
+            client: client.into(),
+            tx: tx.into(),
+            tx_type,
+        };
+        let (_, log) = process_transactions_and_return_client_states_with_log([
+            transaction(1, 1, TransactionType::Deposit(3.into())),
+            transaction(2, 2, TransactionType::Deposit(5.into())),
+            transaction(2, 3, TransactionType::Deposit(7.into())),
+            transaction(1, 1, TransactionType::Dispute),
+            transaction(2, 3, TransactionType::Dispute),
+            transaction(2, 3, TransactionType::Chargeback),
+            transaction(1, 1, TransactionType::Chargeback),
+            transaction(1, 1, TransactionType::Chargeback),
+        ]);
+
+        assert_eq!(
+            reprocess_disputed_transactions(&log),
+            [(2.into(), 7.into()), (1.into(), 3.into())]
+        );
+    }
+
+    #[test]
+    fn test_transaction_log() {
+        let transactions = [
+            Transaction {
+                client: 1.into(),
+                tx: 1.into(),
+                tx_type: TransactionType::Deposit(AmountType::from_str_exact("1.5").unwrap()),
+            },
+            Transaction {
+                client: 1.into(),
+                tx: 2.into(),
+                tx_type: TransactionType::Withdrawal(2.into()),
+            },
+            Transaction {
+                client: 2.into(),
+                tx: 1.into(),
+                tx_type: TransactionType::Dispute,
+            },
+        ];
+
+        let (clients, log) = process_transactions_and_return_client_states_with_log(transactions);
+        assert_eq!((log.applied_count(), log.ignored_count()), (1, 2));
+        assert_eq!(log.replay(), clients);
+        assert_eq!(
+            log.to_csv().unwrap(),
+            "type,client,tx,amount,ignore_reason\n\
+             deposit,1,1,1.5,\n\
+             withdrawal,1,2,2,not enough available funds\n\
+             dispute,2,1,,the client who files the dispute is different from the one who made the deposit\n"
+        );
+        let ignored = log.filter(|_, result| *result != ApplicationResult::Applied);
+        assert_eq!(
+            ignored.to_json().unwrap(),
+            r#"[{"type":"withdrawal","client":1,"tx":2,"amount":"2","ignore_reason":"not enough available funds"},{"type":"dispute","client":2,"tx":1,"amount":null,"ignore_reason":"the client who files the dispute is different from the one who made the deposit"}]"#
+        );
+    }
+
+    #[test]
+    fn test_transaction_log_writer() {
+        let input = "type, client, tx, amount\n\
+                     deposit, 1, 1, 1.5\n\
+                     withdrawal, 1, 2, 2\n\
+                     bogus, 1, 3, 1\n\
+                     dispute, 1, 1,";
+        let mut writer = TransactionLogWriter::new();
+        for chunk in input.as_bytes().chunks(3) {
+            writer.write_all(chunk).unwrap();
+        }
+        assert_eq!(writer.log().entries().len(), 2);
+
+        let log = writer.into_log().unwrap();
+        assert_eq!(
+            log.entries()
+                .iter()
+                .map(|(transaction, result)| (transaction.tx_type.name(), *result))
+                .collect::<Vec<_>>(),
+            [
+                ("deposit", ApplicationResult::Applied),
+                (
+                    "withdrawal",
+                    ApplicationResult::Ignored(IgnoreReason::NotEnoughAvailableFunds)
+                ),
+                ("dispute", ApplicationResult::Applied),
+            ]
+        );
+        assert!(TransactionLogWriter::new()
+            .write_all(b"type,client,tx\n")
+            .is_err());
+    }
+}
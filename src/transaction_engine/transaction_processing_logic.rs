@@ -1,44 +1,160 @@
-use super::{AmountType, ClientId, InputCsvRecord, OutputCsvRecord, TransactionId};
-use std::collections::HashMap;
+#[cfg(test)]
+use super::verify_no_negative_balances;
+use super::{
+    AmountDelta, AmountType, ApplicationResult, AuditEntry, ChangeEvent, ClientId, ClientStateMap,
+    DisputeSummary, IgnoreReason, InputCsvRecord, LockedAccountInfo, OutputCsvRecord,
+    ProcessingConfig, ProcessingError, ProcessingReport, ProcessingWarning, TransactionFilter,
+    TransactionId, TransactionLog, UnknownTransactionPolicy,
+};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap, HashSet};
 use std::convert::TryFrom;
 use std::error::Error;
 use std::iter::IntoIterator;
+use std::mem;
+use std::ops::{Add, AddAssign, Sub};
+use std::str::FromStr;
 use string_error::into_err;
 
-const DEPOSIT: &str = "deposit";
-const WITHDRAWAL: &str = "withdrawal";
-const DISPUTE: &str = "dispute";
-const RESOLVE: &str = "resolve";
-const CHARGEBACK: &str = "chargeback";
+pub(super) const DEPOSIT: &str = "deposit";
+pub(super) const WITHDRAWAL: &str = "withdrawal";
+pub(super) const DISPUTE: &str = "dispute";
+pub(super) const RESOLVE: &str = "resolve";
+pub(super) const CHARGEBACK: &str = "chargeback";
+pub(super) const CORRECTION: &str = "correction";
+
+const DECIMAL_PORTION_LEN: u32 = 4;
+
+/// Reports an ignored transaction on stderr, and as a tracing event with the `tracing` feature.
+pub(super) fn log_ignored(transaction: &Transaction, reason: &IgnoreReason) {
+    eprintln!("{transaction:?} is ignored: {reason}");
+    #[cfg(feature = "tracing")]
+    tracing::info!(?transaction, %reason, "transaction ignored");
+}
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Transaction {
+    pub client: ClientId,
+    pub tx: TransactionId,
+    pub tx_type: TransactionType,
+}
+
+/// Ordered by transaction id alone, so that a stable sort restores the order in which transactions were issued while
+/// keeping a dispute, resolve or chargeback after the deposit it references. Transactions that compare equal aren't
+/// necessarily `==`.
+impl PartialOrd for Transaction {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Transaction {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.tx.cmp(&other.tx)
+    }
+}
+
+/// Sorts by client, then by transaction id, for replaying each client's transactions in order. The sort is stable, so
+/// transactions sharing an id, like a deposit and its dispute, keep their relative order.
+pub fn sorted_transactions(mut txs: Vec<Transaction>) -> Vec<Transaction> {
+    txs.sort_by_key(|transaction| (transaction.client, transaction.tx));
+    txs
+}
+
+/// Keeps only the first transaction of each type per transaction id, e.g. drops a deposit replayed by a feed but keeps
+/// the dispute of a deposit. Returns the kept transactions in their original order, and how many were removed. Note that
+/// a deposit disputed again after a resolve also loses the second dispute.
+pub fn deduplicate_transactions(transactions: Vec<Transaction>) -> (Vec<Transaction>, usize) {
+    let original_len = transactions.len();
+    let mut seen = HashSet::new();
+    let deduplicated: Vec<_> = transactions
+        .into_iter()
+        .filter(|transaction| {
+            seen.insert((transaction.tx, mem::discriminant(&transaction.tx_type)))
+        })
+        .collect();
+    let removed = original_len - deduplicated.len();
+    (deduplicated, removed)
+}
+
+/// The transactions of `client`, plus the disputes, resolves and chargebacks filed by other clients against its
+/// deposits, in their original order: everything that may affect the client. A deposit's owner is taken from the
+/// latest deposit with its id before the referencing transaction, as when processing.
+pub fn transactions_touching_client(
+    transactions: &[Transaction],
     client: ClientId,
-    tx: TransactionId,
-    tx_type: TransactionType,
+) -> Vec<&Transaction> {
+    let mut deposit_owners = HashMap::new();
+    transactions
+        .iter()
+        .filter(|transaction| match transaction.tx_type {
+            Deposit(_) => {
+                deposit_owners.insert(transaction.tx, transaction.client);
+                transaction.client == client
+            }
+            Dispute | Resolve | Chargeback => {
+                transaction.client == client || deposit_owners.get(&transaction.tx) == Some(&client)
+            }
+            Withdrawal(_) | Correction(..) => transaction.client == client,
+        })
+        .collect()
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum TransactionType {
     Deposit(AmountType),
     Withdrawal(AmountType),
     Dispute,
     Resolve,
     Chargeback,
+    /// Operator adjustment of `available` by a signed amount. Without `Force`, a correction that would make
+    /// `available` negative is ignored.
+    Correction(AmountType, Force),
 }
 use TransactionType::*;
 
+type Force = bool;
+
+impl TransactionType {
+    /// The record type of the input, e.g. `"deposit"`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Deposit(_) => DEPOSIT,
+            Withdrawal(_) => WITHDRAWAL,
+            Dispute => DISPUTE,
+            Resolve => RESOLVE,
+            Chargeback => CHARGEBACK,
+            Correction(..) => CORRECTION,
+        }
+    }
+
+    /// The amount given with the transaction; disputes, resolves and chargebacks refer to the amount of a deposit.
+    pub fn amount(&self) -> Option<AmountType> {
+        match *self {
+            Deposit(amount) | Withdrawal(amount) | Correction(amount, _) => Some(amount),
+            Dispute | Resolve | Chargeback => None,
+        }
+    }
+}
+
 impl TryFrom<InputCsvRecord> for Transaction {
     type Error = Box<dyn Error>;
 
     /// Validate input and convert it to the internal, type-safe representation.
+    #[cfg_attr(feature = "tracing", tracing::instrument)]
     fn try_from(value: InputCsvRecord) -> Result<Self, Self::Error> {
+        let amount = match value.amount.as_deref().map(str::trim) {
+            None | Some("") => None,
+            Some(amount) => Some(
+                AmountType::from_str(amount)
+                    .map_err(|e| into_err(format!("{value:?}: invalid amount {amount:?}: {e}")))?,
+            ),
+        };
         let convert_amount = |amount: Option<AmountType>| -> Result<AmountType, Self::Error> {
             match amount {
                 None => Err(into_err(format!("{value:?}: no valid amount found"))),
                 Some(a) => {
                     if AmountType::ZERO < a {
-                        const DECIMAL_PORTION_LEN: u32 = 4;
                         Ok(a.round_dp(DECIMAL_PORTION_LEN))
                     } else {
                         Err(into_err(format!("{value:?}: amount must be positive")))
@@ -51,22 +167,201 @@ impl TryFrom<InputCsvRecord> for Transaction {
             client: value.client,
             tx: value.tx,
             tx_type: match value.record_type.as_str() {
-                DEPOSIT => Deposit(convert_amount(value.amount)?),
-                WITHDRAWAL => Withdrawal(convert_amount(value.amount)?),
+                DEPOSIT => Deposit(convert_amount(amount)?),
+                WITHDRAWAL => Withdrawal(convert_amount(amount)?),
                 DISPUTE => Dispute,
                 RESOLVE => Resolve,
                 CHARGEBACK => Chargeback,
+                CORRECTION => match amount {
+                    None => return Err(into_err(format!("{value:?}: no valid amount found"))),
+                    Some(a) if a.is_zero() => {
+                        return Err(into_err(format!("{value:?}: amount must be non-zero")))
+                    }
+                    Some(a) => Correction(
+                        a.round_dp(DECIMAL_PORTION_LEN),
+                        value.force.unwrap_or(false),
+                    ),
+                },
                 _ => return Err(into_err(format!("{value:?}: unknown record_type"))),
             },
         })
     }
 }
 
-#[derive(Default, Debug, PartialEq)]
+/// The amount of `transaction` as given in the input, with its scale, before it was rounded. `amount` is the raw amount
+/// of the record `transaction` was converted from.
+pub(super) fn written_amount(
+    amount: Option<&str>,
+    transaction: &Transaction,
+) -> Option<AmountType> {
+    transaction.tx_type.amount()?;
+    AmountType::from_str(amount?.trim()).ok()
+}
+
+/// The amount as given in the input and as rounded into `transaction`, if the rounding changed it. `amount` is the
+/// raw amount of the record `transaction` was converted from.
+pub(super) fn precision_loss(
+    amount: Option<&str>,
+    transaction: &Transaction,
+) -> Option<(AmountType, AmountType)> {
+    let original = written_amount(amount, transaction)?;
+    let rounded = transaction.tx_type.amount()?;
+    (original != rounded).then_some((original, rounded))
+}
+
+/// The lenient counterpart of `TryFrom`: a record that doesn't convert gives `None`, with the error logged to stderr.
+impl From<InputCsvRecord> for Option<Transaction> {
+    fn from(value: InputCsvRecord) -> Self {
+        Transaction::try_from(value)
+            .map_err(|e| eprintln!("conversion (InputCsvRecord -> Transaction) error: {e}"))
+            .ok()
+    }
+}
+
+/// Hashable, e.g. to find identical states across snapshots with a `HashSet`. Amounts that differ only in scale, like
+/// `1.0` and `1.00`, are equal and hash the same.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Hash)]
 pub struct ClientState {
     pub available: AmountType,
     pub held: AmountType,
     pub locked: bool,
+    /// Transactions that arrived while the client was locked, in arrival order, so that they can be audited and
+    /// processed once the lock is lifted.
+    pub pending_locked_transactions: Vec<Transaction>,
+    /// How many transactions of each type were applied to the client; ignored ones don't count.
+    #[cfg(feature = "detailed-stats")]
+    pub deposit_count: u32,
+    #[cfg(feature = "detailed-stats")]
+    pub withdrawal_count: u32,
+    #[cfg(feature = "detailed-stats")]
+    pub dispute_count: u32,
+    #[cfg(feature = "detailed-stats")]
+    pub resolve_count: u32,
+    #[cfg(feature = "detailed-stats")]
+    pub chargeback_count: u32,
+    /// The currency of the amounts, e.g. `"USD"`, if known; see `apply_exchange_rate`.
+    #[cfg(feature = "multi-currency")]
+    pub currency: Option<String>,
+}
+
+impl ClientState {
+    /// The conservative spendable balance: `available` minus holds that were initiated but not yet moved to `held`.
+    /// No transaction initiates such a hold yet, so for now this is always `available`.
+    pub fn effective_available(&self) -> AmountType {
+        self.available
+    }
+
+    /// Whether a withdrawal of `amount` would apply.
+    pub fn can_withdraw(&self, amount: AmountType) -> bool {
+        !self.locked && amount <= self.available
+    }
+
+    /// Whether a dispute of a deposit of `deposit_amount` made by this client would apply, provided the deposit isn't
+    /// already under dispute.
+    pub fn can_dispute(&self, deposit_amount: AmountType) -> bool {
+        !self.locked && deposit_amount <= self.available
+    }
+
+    /// A copy with `available` replaced, e.g. `state.clone().with_available(state.available - fee)`.
+    pub fn with_available(mut self, available: AmountType) -> Self {
+        self.available = available;
+        self
+    }
+
+    pub fn with_held(mut self, held: AmountType) -> Self {
+        self.held = held;
+        self
+    }
+
+    pub fn with_locked(mut self, locked: bool) -> Self {
+        self.locked = locked;
+        self
+    }
+
+    #[cfg(feature = "detailed-stats")]
+    fn count(&mut self, tx_type: &TransactionType) {
+        match tx_type {
+            Deposit(_) => self.deposit_count += 1,
+            Withdrawal(_) => self.withdrawal_count += 1,
+            Dispute => self.dispute_count += 1,
+            Resolve => self.resolve_count += 1,
+            Chargeback => self.chargeback_count += 1,
+            Correction(..) => {}
+        }
+    }
+
+    /// Moves the whole available balance out, e.g. to close the account, and returns it. Fails while funds are held
+    /// by open disputes, unless `force` is set, in which case the held funds stay where they are.
+    pub fn withdraw_all(&mut self, force: bool) -> Result<AmountType, ProcessingError> {
+        if AmountType::ZERO < self.held && !force {
+            return Err(ProcessingError::FundsHeld(self.held));
+        }
+        Ok(std::mem::take(&mut self.available))
+    }
+
+    /// Releases everything held back to available, e.g. when a bug left funds stuck in held, and returns the released
+    /// amount. The deposits under dispute stay under dispute; this bypasses the dispute lifecycle entirely.
+    #[cfg(feature = "admin-operations")]
+    pub fn reset_held(&mut self) -> AmountType {
+        let released = std::mem::take(&mut self.held);
+        eprintln!("warning: {released} released from held outside of the dispute lifecycle");
+        self.available += released;
+        released
+    }
+}
+
+/// Merges the states of the same client produced by processing shards: balances are summed and a lock on either
+/// side is kept. This is only meaningful when the two states come from disjoint transaction histories, e.g.
+/// separate date ranges, since a dispute in one shard can't see a deposit made in the other.
+impl Add for ClientState {
+    type Output = ClientState;
+
+    fn add(mut self, rhs: ClientState) -> Self::Output {
+        self += rhs;
+        self
+    }
+}
+
+impl AddAssign for ClientState {
+    fn add_assign(&mut self, rhs: ClientState) {
+        self.available += rhs.available;
+        self.held += rhs.held;
+        self.locked |= rhs.locked;
+        self.pending_locked_transactions
+            .extend(rhs.pending_locked_transactions);
+        #[cfg(feature = "detailed-stats")]
+        {
+            self.deposit_count += rhs.deposit_count;
+            self.withdrawal_count += rhs.withdrawal_count;
+            self.dispute_count += rhs.dispute_count;
+            self.resolve_count += rhs.resolve_count;
+            self.chargeback_count += rhs.chargeback_count;
+        }
+        #[cfg(feature = "multi-currency")]
+        if self.currency.is_none() {
+            self.currency = rhs.currency;
+        }
+    }
+}
+
+/// The balance changes between two states of a client, e.g. for reconciliation. Whether the lock changed is not a
+/// difference in amounts, so it is left to the caller to compare `locked`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ClientStateDelta {
+    pub available_delta: AmountDelta,
+    pub held_delta: AmountDelta,
+}
+
+/// `after - before` is the change from `before` to `after`.
+impl Sub for ClientState {
+    type Output = ClientStateDelta;
+
+    fn sub(self, rhs: ClientState) -> Self::Output {
+        ClientStateDelta {
+            available_delta: AmountDelta::between(rhs.available, self.available),
+            held_delta: AmountDelta::between(rhs.held, self.held),
+        }
+    }
 }
 
 impl From<(ClientId, ClientState)> for OutputCsvRecord {
@@ -77,156 +372,736 @@ impl From<(ClientId, ClientState)> for OutputCsvRecord {
             held: client_state.held,
             total: client_state.available + client_state.held,
             locked: client_state.locked,
+            #[cfg(feature = "detailed-stats")]
+            deposit_count: None,
+            #[cfg(feature = "detailed-stats")]
+            withdrawal_count: None,
+            #[cfg(feature = "detailed-stats")]
+            dispute_count: None,
+            #[cfg(feature = "detailed-stats")]
+            resolve_count: None,
+            #[cfg(feature = "detailed-stats")]
+            chargeback_count: None,
+        }
+    }
+}
+
+/// Turns a client's final balance into an opening deposit for a follow-up run, with the client id as the transaction
+/// id, so the follow-up input must not reuse those ids. There is no transaction that locks a client, so locked clients
+/// are rejected rather than silently unlocked. Clients without a positive balance are rejected too, since a deposit
+/// must be positive; leaving them out of the follow-up run gives them the same empty state.
+impl TryFrom<OutputCsvRecord> for InputCsvRecord {
+    type Error = Box<dyn Error>;
+
+    fn try_from(value: OutputCsvRecord) -> Result<Self, Self::Error> {
+        if value.locked {
+            return Err(into_err(format!(
+                "{value:?}: a locked client can't be carried over"
+            )));
         }
+        let balance = value.available + value.held;
+        if balance <= AmountType::ZERO {
+            return Err(into_err(format!(
+                "{value:?}: a client without a positive balance can't be carried over"
+            )));
+        }
+        Ok(InputCsvRecord {
+            record_type: DEPOSIT.to_owned(),
+            client: value.client,
+            tx: u32::from(u16::from(value.client)).into(),
+            amount: Some(balance.to_string()),
+            force: None,
+        })
     }
 }
 
+type UnderDispute = bool;
+
+/// Per-client sum of the amounts of applied deposits or withdrawals.
+pub type FlowTotals = HashMap<ClientId, AmountType>;
+
+/// Every deposit seen so far, keyed by its transaction id, so that it can be disputed later.
+pub type DepositLog = HashMap<TransactionId, (ClientId, AmountType, UnderDispute)>;
+
+/// Removes every deposit that is not currently under dispute (never disputed, or resolved) to bound memory usage.
+/// Those deposits can no longer be disputed afterwards, so only compact when late disputes are no longer expected.
+pub fn compact_deposit_log(log: &mut DepositLog) {
+    log.retain(|_, &mut (_, _, under_dispute)| under_dispute);
+}
+
+/// The smallest transaction id under dispute, as a proxy for the longest-standing open dispute, since transactions
+/// carry no timestamps.
+pub fn oldest_open_dispute(deposit_log: &DepositLog) -> Option<TransactionId> {
+    deposit_log
+        .iter()
+        .filter(|&(_, &(_, _, under_dispute))| under_dispute)
+        .map(|(&tx, _)| tx)
+        .min()
+}
+
 /// In my opinion, combining the Read trait with the laziness of Iterator guarantees that this function process transactions
 /// as a stream. Data will not be totally loaded into memory at once. If a TcpStream's data rate is
 /// low, this function should be synchronously blocked from time to time.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(skip(transactions), fields(transaction_count))
+)]
 pub fn process_transactions_and_return_client_states(
     transactions: impl IntoIterator<Item = Transaction>,
 ) -> HashMap<ClientId, ClientState> {
-    let mut clients = HashMap::<ClientId, ClientState>::new();
+    let config = ProcessingConfig {
+        unknown_transaction_policy: UnknownTransactionPolicy::Ignore,
+        ..Default::default()
+    };
+    let report = process_transactions(without_source_lines(transactions), &config)
+        .expect("the Ignore policy never fails");
+    #[cfg(feature = "tracing")]
+    tracing::Span::current().record("transaction_count", report.stats.transactions_read);
+    report.client_states.into()
+}
 
-    type UnderDispute = bool;
-    let mut deposit_transactions_seen =
-        HashMap::<TransactionId, (ClientId, AmountType, UnderDispute)>::new();
+/// How far `replay_transactions_up_to` replays. Transactions carry no timestamps, so they can only be counted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplayLimit {
+    /// The first this many transactions, e.g. `ByCount(5000)` for the states right after transaction #5000.
+    ByCount(usize),
+}
 
-    for transaction in transactions {
-        let client = transaction.client;
-        let tx = transaction.tx;
+/// The client states after the transactions within `limit`, e.g. to look up a balance at some point of a logged run.
+/// Only the transactions up to the limit are processed.
+pub fn replay_transactions_up_to(
+    transactions: &[Transaction],
+    limit: ReplayLimit,
+) -> HashMap<ClientId, ClientState> {
+    let ReplayLimit::ByCount(count) = limit;
+    process_transactions_and_return_client_states(transactions.iter().take(count).cloned())
+}
 
-        let client_state = clients.entry(client).or_default();
-        if client_state.locked {
-            eprintln!("{transaction:?} is ignored: client is locked");
-            continue;
+/// Same as `process_transactions_and_return_client_states`, but also returns how many transactions were ignored, so
+/// that callers can tell a clean run from one that skipped transactions without parsing stderr.
+pub fn process_transactions_and_return_client_states_with_error_count(
+    transactions: impl IntoIterator<Item = Transaction>,
+) -> (HashMap<ClientId, ClientState>, usize) {
+    let config = ProcessingConfig {
+        unknown_transaction_policy: UnknownTransactionPolicy::Ignore,
+        ..Default::default()
+    };
+    let report = process_transactions(without_source_lines(transactions), &config)
+        .expect("the Ignore policy never fails");
+    (
+        report.client_states.into(),
+        report.stats.transactions_ignored as usize,
+    )
+}
+
+/// Same as `process_transactions_and_return_client_states`, but `config` decides how referential integrity
+/// violations are handled, and the warnings raised along the way are returned with the client states.
+pub fn process_transactions_with_config(
+    transactions: impl IntoIterator<Item = Transaction>,
+    config: &ProcessingConfig,
+) -> Result<(HashMap<ClientId, ClientState>, Vec<ProcessingWarning>), ProcessingError> {
+    let report = process_transactions(without_source_lines(transactions), config)?;
+    Ok((report.client_states.into(), report.warnings))
+}
+
+/// Same as `process_transactions_and_return_client_states`, but also returns the totals of the deposits and the
+/// withdrawals that were applied for each client, for use with `net_flow_report`.
+pub fn process_transactions_and_return_flow_totals(
+    transactions: impl IntoIterator<Item = Transaction>,
+) -> (ClientStateMap, FlowTotals, FlowTotals) {
+    let config = ProcessingConfig {
+        unknown_transaction_policy: UnknownTransactionPolicy::Ignore,
+        ..Default::default()
+    };
+    let report = process_transactions(without_source_lines(transactions), &config)
+        .expect("the Ignore policy never fails");
+    (
+        report.client_states,
+        report.deposit_totals,
+        report.withdrawal_totals,
+    )
+}
+
+/// Same as `process_transactions_and_return_client_states`, but only the transactions matching `filter` are
+/// processed. The others are dropped silently, as if they were never in the input.
+pub fn process_transactions_filtered(
+    transactions: impl IntoIterator<Item = Transaction>,
+    filter: &dyn TransactionFilter,
+) -> HashMap<ClientId, ClientState> {
+    process_transactions_and_return_client_states(
+        transactions
+            .into_iter()
+            .filter(|transaction| filter.matches(transaction)),
+    )
+}
+
+/// Same as `process_transactions_and_return_client_states`, but only returns the `n` clients with the largest
+/// total (`available + held`), largest first. Every client's state is still needed while processing; a min-heap of
+/// size `n` then picks the winners without sorting all of them.
+pub fn process_transactions_and_return_top_n(
+    transactions: impl IntoIterator<Item = Transaction>,
+    n: usize,
+) -> Vec<(ClientId, ClientState)> {
+    let mut clients = process_transactions_and_return_client_states(transactions);
+
+    let mut top_n = BinaryHeap::new();
+    for (&client_id, client_state) in &clients {
+        top_n.push(Reverse((
+            client_state.available + client_state.held,
+            client_id,
+        )));
+        if top_n.len() > n {
+            top_n.pop();
         }
+    }
+
+    top_n
+        .into_sorted_vec()
+        .into_iter()
+        .map(|Reverse((_, client_id))| {
+            let client_state = clients.remove(&client_id).expect("picked from clients");
+            (client_id, client_state)
+        })
+        .collect()
+}
+
+/// Everything a processing run produces, with the default config plus audit entries. The audit holds one entry per
+/// transaction, so this does not run in constant memory like `process_transactions_and_return_client_states`.
+pub fn process_transactions_into_report(
+    transactions: impl IntoIterator<Item = Transaction>,
+) -> ProcessingReport {
+    let config = ProcessingConfig {
+        record_audit_entries: true,
+        ..Default::default()
+    };
+    process_transactions(without_source_lines(transactions), &config)
+        .expect("the Warn policy never fails")
+}
+
+/// Same as `process_transactions_and_return_client_states`, plus a `TransactionLog` of every transaction and what became
+/// of it. The log holds one entry per transaction, so this does not run in constant memory.
+pub fn process_transactions_and_return_client_states_with_log(
+    transactions: impl IntoIterator<Item = Transaction>,
+) -> (HashMap<ClientId, ClientState>, TransactionLog) {
+    let config = ProcessingConfig {
+        unknown_transaction_policy: UnknownTransactionPolicy::Ignore,
+        record_audit_entries: true,
+        ..Default::default()
+    };
+    let report = process_transactions(without_source_lines(transactions), &config)
+        .expect("the Ignore policy never fails");
+    let mut log = TransactionLog::default();
+    for audit_entry in report.audit_entries {
+        let result = match audit_entry.ignore_reason {
+            None => ApplicationResult::Applied,
+            Some(reason) => ApplicationResult::Ignored(reason),
+        };
+        log.push(audit_entry.transaction, result);
+    }
+    (report.client_states.into(), log)
+}
+
+fn without_source_lines(
+    transactions: impl IntoIterator<Item = Transaction>,
+) -> impl Iterator<Item = (Transaction, Option<u64>)> {
+    transactions
+        .into_iter()
+        .map(|transaction| (transaction, None))
+}
 
-        match transaction.tx_type {
-            Deposit(amount) => {
-                deposit_transactions_seen.insert(tx, (client, amount, false));
-                client_state.available += amount;
+/// Each transaction comes with the 1-based line it was read from, if known, which is attached to its warnings.
+pub(super) fn process_transactions(
+    transactions: impl IntoIterator<Item = (Transaction, Option<u64>)>,
+    config: &ProcessingConfig,
+) -> Result<ProcessingReport, ProcessingError> {
+    let mut report = ProcessingReport::default();
+    let mut clients = HashMap::<ClientId, ClientState>::new();
+
+    let mut deposit_transactions_seen = DepositLog::new();
+    // Forced corrections may make `available` negative on purpose, see `verify_no_negative_balances` below.
+    #[cfg(test)]
+    let mut force_corrected_clients = HashSet::new();
+
+    for (transaction, source_line) in transactions {
+        report.stats.transactions_read += 1;
+        report.transaction_type_stats.record(&transaction.tx_type);
+        if let Some(types) = &config.only_transaction_types {
+            if !types.iter().any(|filter| filter.matches(&transaction)) {
+                report.warnings.push(ProcessingWarning::FilteredByType {
+                    client: transaction.client,
+                    tx: transaction.tx,
+                    source_line,
+                });
+                continue;
             }
-            Withdrawal(amount) => {
-                if client_state.available < amount {
-                    eprintln!("{transaction:?} is ignored: not enough available funds");
-                } else {
-                    client_state.available -= amount;
-                }
+        }
+        if let Deposit(_) = transaction.tx_type {
+            if config.strict_mode && deposit_transactions_seen.contains_key(&transaction.tx) {
+                return Err(ProcessingError::DuplicateTransactionId(transaction.tx));
+            }
+        }
+        let balances = |clients: &HashMap<ClientId, ClientState>| {
+            clients
+                .get(&transaction.client)
+                .map_or((AmountType::ZERO, AmountType::ZERO), |client_state| {
+                    (client_state.available, client_state.held)
+                })
+        };
+        let (available_before, held_before) = balances(&clients);
+
+        let result = match (&config.strict_amounts, transaction.tx_type.amount()) {
+            (Some(validator), Some(amount)) if !validator.accepts(amount) => {
+                Err(IgnoreReason::AmountScaleMismatch)
             }
-            Dispute => match deposit_transactions_seen.get_mut(&tx) {
-                None => {
-                    eprintln!("{transaction:?} is ignored: no previous deposit transaction found");
+            _ => apply_transaction(&mut clients, &mut deposit_transactions_seen, &transaction),
+        };
+
+        if config.record_audit_entries {
+            let (available_after, held_after) = balances(&clients);
+            report.audit_entries.push(AuditEntry {
+                transaction: transaction.clone(),
+                ignore_reason: result.err(),
+                available_delta: AmountDelta::between(available_before, available_after),
+                held_delta: AmountDelta::between(held_before, held_after),
+            });
+        }
+        match result {
+            Ok(()) => match transaction.tx_type {
+                Deposit(amount) => {
+                    *report.deposit_totals.entry(transaction.client).or_default() += amount
                 }
-                Some(&mut (deposit_client, deposit_amount, ref mut deposit_under_dispute)) => {
-                    if *deposit_under_dispute {
-                        eprintln!("{transaction:?} is ignored: already under dispute");
-                    } else if client != deposit_client {
-                        eprintln!("{transaction:?} is ignored: the client who files the dispute is different from the one who made the deposit");
-                    } else if client_state.available < deposit_amount {
-                        eprintln!("{transaction:?} is ignored: can't file this dispute due to not enough available funds");
-                    } else {
-                        client_state.available -= deposit_amount;
-                        client_state.held += deposit_amount;
-                        *deposit_under_dispute = true;
-                    }
+                Withdrawal(amount) => {
+                    *report
+                        .withdrawal_totals
+                        .entry(transaction.client)
+                        .or_default() += amount
                 }
+                #[cfg(test)]
+                Correction(_, true) => {
+                    force_corrected_clients.insert(transaction.client);
+                }
+                _ => {}
             },
-            Resolve => match deposit_transactions_seen.get_mut(&tx) {
-                None => {
-                    eprintln!("{transaction:?} is ignored: no previous dispute transaction found");
+            Err(reason) => {
+                report.stats.transactions_ignored += 1;
+                log_ignored(&transaction, &reason);
+                if let IgnoreReason::NoDepositFound | IgnoreReason::NoDisputeFound = reason {
+                    on_unknown_transaction(
+                        &transaction,
+                        source_line,
+                        config,
+                        &mut report.warnings,
+                    )?;
                 }
-                Some(&mut (dispute_client, dispute_amount, ref mut deposit_under_dispute)) => {
-                    if !*deposit_under_dispute {
-                        eprintln!("{transaction:?} is ignored: not under dispute");
-                    } else if client != dispute_client {
-                        eprintln!("{transaction:?} is ignored: the client who files the resolve is different from the one who filed the dispute");
-                    } else {
-                        client_state.available += dispute_amount;
-                        client_state.held -= dispute_amount;
-                        *deposit_under_dispute = false;
-                    }
+            }
+        }
+    }
+
+    #[cfg(test)]
+    if let Err(reports) = verify_no_negative_balances(&clients) {
+        let reports: Vec<_> = reports
+            .into_iter()
+            .filter(|report| !force_corrected_clients.contains(&report.client_id))
+            .collect();
+        assert!(reports.is_empty(), "negative balances: {reports:?}");
+    }
+    report.stats.record_clients(&clients);
+    report.client_states = clients.into();
+    Ok(report)
+}
+
+/// Same as `process_transactions_and_return_client_states`, but calls `on_batch_complete` with the client states so
+/// far and the 1-based batch number after every `batch_size` transactions, and once more after a final partial batch,
+/// e.g. to report progress. Panics if `batch_size` is 0.
+pub fn process_transactions_in_batches<I>(
+    transactions: I,
+    batch_size: usize,
+    mut on_batch_complete: impl FnMut(&HashMap<ClientId, ClientState>, usize),
+) -> HashMap<ClientId, ClientState>
+where
+    I: IntoIterator<Item = Transaction>,
+{
+    assert!(0 < batch_size, "batch_size must be positive");
+    let mut clients = HashMap::new();
+    let mut deposit_transactions_seen = DepositLog::new();
+
+    let mut batch_len = 0;
+    let mut batch_number = 0;
+    for transaction in transactions {
+        if let Err(reason) =
+            apply_transaction(&mut clients, &mut deposit_transactions_seen, &transaction)
+        {
+            log_ignored(&transaction, &reason);
+        }
+        batch_len += 1;
+        if batch_len == batch_size {
+            batch_len = 0;
+            batch_number += 1;
+            on_batch_complete(&clients, batch_number);
+        }
+    }
+    if 0 < batch_len {
+        on_batch_complete(&clients, batch_number + 1);
+    }
+    clients
+}
+
+/// Same as `process_transactions_and_return_client_states`, but returns every change the transactions made to the
+/// accounts, in order, instead of the final states.
+pub fn process_transactions_and_return_change_events(
+    transactions: impl IntoIterator<Item = Transaction>,
+) -> Vec<ChangeEvent> {
+    let mut clients = HashMap::new();
+    let mut deposit_transactions_seen = DepositLog::new();
+
+    let mut events = Vec::new();
+    for transaction in transactions {
+        let before = clients.get(&transaction.client).map_or(
+            (AmountType::ZERO, AmountType::ZERO, false),
+            |client_state: &ClientState| {
+                (
+                    client_state.available,
+                    client_state.held,
+                    client_state.locked,
+                )
+            },
+        );
+        if let Err(reason) =
+            apply_transaction(&mut clients, &mut deposit_transactions_seen, &transaction)
+        {
+            log_ignored(&transaction, &reason);
+        }
+        events.extend(ChangeEvent::between(
+            transaction.client,
+            before,
+            &clients[&transaction.client],
+        ));
+    }
+    events
+}
+
+/// Same as `process_transactions_and_return_client_states`, but only returns the locked clients, sorted by client id,
+/// with the chargeback that locked each of them.
+pub fn process_transactions_and_return_locked_accounts(
+    transactions: impl IntoIterator<Item = Transaction>,
+) -> Vec<LockedAccountInfo> {
+    let mut clients = HashMap::new();
+    let mut deposit_transactions_seen = DepositLog::new();
+
+    let mut locking_chargebacks = HashMap::new();
+    for transaction in transactions {
+        let disputed_amount = deposit_transactions_seen
+            .get(&transaction.tx)
+            .map(|&(_, amount, _)| amount);
+        match apply_transaction(&mut clients, &mut deposit_transactions_seen, &transaction) {
+            Ok(()) => {
+                if let (Chargeback, Some(amount)) = (&transaction.tx_type, disputed_amount) {
+                    locking_chargebacks.insert(transaction.client, (transaction.tx, amount));
                 }
+            }
+            Err(reason) => log_ignored(&transaction, &reason),
+        }
+    }
+
+    let mut locked_accounts: Vec<_> = locking_chargebacks
+        .into_iter()
+        .map(
+            |(client_id, (chargeback_tx_id, chargeback_amount))| LockedAccountInfo {
+                client_id,
+                chargeback_tx_id,
+                chargeback_amount,
+                client_state: clients
+                    .remove(&client_id)
+                    .expect("a chargeback creates its client"),
             },
-            Chargeback => match deposit_transactions_seen.get(&tx) {
-                None => {
-                    eprintln!("{transaction:?} is ignored: no previous dispute transaction found");
+        )
+        .collect();
+    locked_accounts.sort_unstable_by_key(|locked_account| locked_account.client_id);
+    locked_accounts
+}
+
+/// The peak of the total held across all clients over the processing, with the transaction that reached it first, e.g.
+/// to size the liquidity reserve that covers all disputed funds. `(0, None)` if nothing was ever held.
+pub fn max_concurrent_holds(
+    transactions: impl IntoIterator<Item = Transaction>,
+) -> (AmountType, Option<Transaction>) {
+    let mut clients = HashMap::new();
+    let mut deposit_transactions_seen = DepositLog::new();
+    let mut total_held = AmountType::ZERO;
+    let mut peak = (AmountType::ZERO, None);
+    for transaction in transactions {
+        let held = |clients: &HashMap<ClientId, ClientState>| {
+            clients
+                .get(&transaction.client)
+                .map_or(AmountType::ZERO, |client_state| client_state.held)
+        };
+        let held_before = held(&clients);
+        match apply_transaction(&mut clients, &mut deposit_transactions_seen, &transaction) {
+            Ok(()) => {
+                total_held += held(&clients) - held_before;
+                if peak.0 < total_held {
+                    peak = (total_held, Some(transaction));
                 }
-                Some(&(dispute_client, dispute_amount, deposit_under_dispute)) => {
-                    if !deposit_under_dispute {
-                        eprintln!("{transaction:?} is ignored: not under dispute");
-                    } else if client != dispute_client {
-                        eprintln!("{transaction:?} is ignored: the client who files the chargeback is different from the one who filed the dispute");
-                    } else {
-                        client_state.held -= dispute_amount;
-                        client_state.locked = true;
-                        deposit_transactions_seen.remove(&tx);
-                    }
+            }
+            Err(reason) => log_ignored(&transaction, &reason),
+        }
+    }
+    peak
+}
+
+/// Same as `process_transactions_and_return_client_states`, but only returns the clients with open disputes at the
+/// end, sorted by client id.
+pub fn process_transactions_and_return_dispute_summary(
+    transactions: impl IntoIterator<Item = Transaction>,
+) -> Vec<DisputeSummary> {
+    let mut clients = HashMap::new();
+    let mut deposit_transactions_seen = DepositLog::new();
+    for transaction in transactions {
+        if let Err(reason) =
+            apply_transaction(&mut clients, &mut deposit_transactions_seen, &transaction)
+        {
+            log_ignored(&transaction, &reason);
+        }
+    }
+
+    let mut open_disputes = HashMap::<ClientId, Vec<TransactionId>>::new();
+    for (&tx, &(client, _, under_dispute)) in &deposit_transactions_seen {
+        if under_dispute {
+            open_disputes.entry(client).or_default().push(tx);
+        }
+    }
+    let mut summaries: Vec<_> = open_disputes
+        .into_iter()
+        .map(|(client, mut open_disputes)| {
+            open_disputes.sort_unstable();
+            DisputeSummary {
+                client,
+                open_disputes,
+                total_held: clients[&client].held,
+            }
+        })
+        .collect();
+    summaries.sort_unstable_by_key(|summary| summary.client);
+    summaries
+}
+
+/// Runs the full state machine on scratch state and returns only the transactions that would be ignored, with the
+/// reason. Nothing is logged; useful for validating a batch before committing it.
+pub fn process_transactions_dry_run(
+    transactions: impl IntoIterator<Item = Transaction>,
+) -> Vec<(Transaction, IgnoreReason)> {
+    let mut clients = HashMap::new();
+    let mut deposit_transactions_seen = DepositLog::new();
+
+    transactions
+        .into_iter()
+        .filter_map(|transaction| {
+            apply_transaction(&mut clients, &mut deposit_transactions_seen, &transaction)
+                .err()
+                .map(|reason| (transaction, reason))
+        })
+        .collect()
+}
+
+/// The outcome of `simulate_transaction`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SimulationResult {
+    Applied(ClientState),
+    Rejected(IgnoreReason),
+}
+
+/// What `tx` would do to `current_state`, the state of `tx.client`, given `deposit_log`, without changing either; e.g.
+/// to preview a withdrawal before committing it.
+pub fn simulate_transaction(
+    current_state: &ClientState,
+    deposit_log: &DepositLog,
+    tx: &Transaction,
+) -> SimulationResult {
+    let mut clients = HashMap::from([(tx.client, current_state.clone())]);
+    // The transaction can only touch the deposit it references, so that is all that needs copying.
+    let mut deposit_log: DepositLog = deposit_log
+        .get_key_value(&tx.tx)
+        .map(|(&tx, &deposit)| (tx, deposit))
+        .into_iter()
+        .collect();
+    match apply_transaction(&mut clients, &mut deposit_log, tx) {
+        Ok(()) => {
+            SimulationResult::Applied(clients.remove(&tx.client).expect("the client was inserted"))
+        }
+        Err(reason) => SimulationResult::Rejected(reason),
+    }
+}
+
+/// The state machine for a single transaction. A client seen for the first time gets a default state, even if the
+/// transaction ends up ignored.
+pub(super) fn apply_transaction(
+    clients: &mut HashMap<ClientId, ClientState>,
+    deposit_transactions_seen: &mut DepositLog,
+    transaction: &Transaction,
+) -> Result<(), IgnoreReason> {
+    let client = transaction.client;
+    let tx = transaction.tx;
+
+    let client_state = clients.entry(client).or_default();
+    if client_state.locked {
+        client_state
+            .pending_locked_transactions
+            .push(transaction.clone());
+        return Err(IgnoreReason::ClientLocked);
+    }
+
+    match transaction.tx_type {
+        Deposit(amount) => {
+            deposit_transactions_seen.insert(tx, (client, amount, false));
+            client_state.available += amount;
+        }
+        Withdrawal(amount) => {
+            if !client_state.can_withdraw(amount) {
+                return Err(IgnoreReason::NotEnoughAvailableFunds);
+            }
+            client_state.available -= amount;
+        }
+        Dispute => match deposit_transactions_seen.get_mut(&tx) {
+            None => return Err(IgnoreReason::NoDepositFound),
+            Some(&mut (deposit_client, deposit_amount, ref mut deposit_under_dispute)) => {
+                if *deposit_under_dispute {
+                    return Err(IgnoreReason::AlreadyUnderDispute);
+                } else if client != deposit_client {
+                    return Err(IgnoreReason::DisputeClientMismatch);
+                } else if !client_state.can_dispute(deposit_amount) {
+                    return Err(IgnoreReason::NotEnoughAvailableFundsToDispute);
                 }
-            },
+                client_state.available -= deposit_amount;
+                client_state.held += deposit_amount;
+                *deposit_under_dispute = true;
+            }
+        },
+        Resolve => match deposit_transactions_seen.get_mut(&tx) {
+            None => return Err(IgnoreReason::NoDisputeFound),
+            Some(&mut (dispute_client, dispute_amount, ref mut deposit_under_dispute)) => {
+                if !*deposit_under_dispute {
+                    return Err(IgnoreReason::NotUnderDispute);
+                } else if client != dispute_client {
+                    return Err(IgnoreReason::ResolveClientMismatch);
+                }
+                client_state.available += dispute_amount;
+                client_state.held -= dispute_amount;
+                *deposit_under_dispute = false;
+            }
+        },
+        Chargeback => match deposit_transactions_seen.get(&tx) {
+            None => return Err(IgnoreReason::NoDisputeFound),
+            Some(&(dispute_client, dispute_amount, deposit_under_dispute)) => {
+                if !deposit_under_dispute {
+                    return Err(IgnoreReason::NotUnderDispute);
+                } else if client != dispute_client {
+                    return Err(IgnoreReason::ChargebackClientMismatch);
+                }
+                client_state.held -= dispute_amount;
+                client_state.locked = true;
+                deposit_transactions_seen.remove(&tx);
+            }
+        },
+        Correction(amount, force) => {
+            if client_state.available + amount < AmountType::ZERO && !force {
+                return Err(IgnoreReason::CorrectionWouldBeNegative);
+            }
+            client_state.available += amount;
         }
     }
 
-    clients
+    #[cfg(feature = "detailed-stats")]
+    client_state.count(&transaction.tx_type);
+    Ok(())
+}
+
+fn on_unknown_transaction(
+    transaction: &Transaction,
+    source_line: Option<u64>,
+    config: &ProcessingConfig,
+    warnings: &mut Vec<ProcessingWarning>,
+) -> Result<(), ProcessingError> {
+    let (client, tx) = (transaction.client, transaction.tx);
+    match config.unknown_transaction_policy {
+        UnknownTransactionPolicy::Ignore => {}
+        UnknownTransactionPolicy::Warn => warnings.push(ProcessingWarning::UnknownTransaction {
+            client,
+            tx,
+            source_line,
+        }),
+        UnknownTransactionPolicy::Error => {
+            return Err(ProcessingError::UnknownTransaction { client, tx })
+        }
+    }
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
+    use super::super::test_helpers::assert_client_state_equals;
+    use super::super::{ByTransactionType, Not, StrictAmountValidator};
     use super::*;
 
     #[test]
     fn test_deposit_and_withdrawal() {
         let clients = process_transactions_and_return_client_states([
             Transaction {
-                client: 3,
-                tx: 2,
+                client: 3.into(),
+                tx: 2.into(),
                 tx_type: Deposit(AmountType::from_str_exact("2.3456").unwrap()),
             },
             Transaction {
-                client: 1,
-                tx: 1,
+                client: 1.into(),
+                tx: 1.into(),
                 tx_type: Deposit(AmountType::from_str_exact("10.3").unwrap()),
             },
             Transaction {
-                client: 3,
-                tx: 5,
+                client: 3.into(),
+                tx: 5.into(),
                 tx_type: Deposit(AmountType::from_str_exact("0.0001").unwrap()),
             },
             Transaction {
-                client: 3,
-                tx: 4,
+                client: 3.into(),
+                tx: 4.into(),
                 tx_type: Withdrawal(AmountType::from_str_exact("1.1").unwrap()),
             },
             Transaction {
-                client: 3,
-                tx: 6,
+                client: 3.into(),
+                tx: 6.into(),
                 tx_type: Withdrawal(AmountType::from_str_exact("100.1").unwrap()),
             },
         ]);
 
-        assert_eq!(
-            clients,
-            [
+        assert_client_state_equals(
+            &clients,
+            &[
                 (
-                    3,
+                    3.into(),
                     ClientState {
                         available: AmountType::from_str_exact("1.2457").unwrap(),
                         held: AmountType::ZERO,
                         locked: false,
-                    }
+                        #[cfg(feature = "detailed-stats")]
+                        deposit_count: 2,
+                        #[cfg(feature = "detailed-stats")]
+                        withdrawal_count: 1,
+                        ..Default::default()
+                    },
                 ),
                 (
-                    1,
+                    1.into(),
                     ClientState {
                         available: AmountType::from_str_exact("10.3").unwrap(),
                         held: AmountType::ZERO,
                         locked: false,
-                    }
+                        #[cfg(feature = "detailed-stats")]
+                        deposit_count: 1,
+                        ..Default::default()
+                    },
                 ),
             ]
             .into_iter()
-            .collect()
+            .collect(),
         );
     }
 
@@ -234,64 +1109,72 @@ mod tests {
     fn test_dispute() {
         let clients = process_transactions_and_return_client_states([
             Transaction {
-                client: 3,
-                tx: 2,
+                client: 3.into(),
+                tx: 2.into(),
                 tx_type: Deposit(AmountType::from_str_exact("2.3456").unwrap()),
             },
             Transaction {
-                client: 3,
-                tx: 4,
+                client: 3.into(),
+                tx: 4.into(),
                 tx_type: Withdrawal(AmountType::from_str_exact("2").unwrap()),
             },
             Transaction {
-                client: 4,
-                tx: 2,
+                client: 4.into(),
+                tx: 2.into(),
                 tx_type: Dispute,
             },
             Transaction {
-                client: 3,
-                tx: 100,
+                client: 3.into(),
+                tx: 100.into(),
                 tx_type: Dispute,
             },
             Transaction {
-                client: 3,
-                tx: 2,
+                client: 3.into(),
+                tx: 2.into(),
                 tx_type: Dispute,
             },
             Transaction {
-                client: 3,
-                tx: 10,
+                client: 3.into(),
+                tx: 10.into(),
                 tx_type: Deposit(AmountType::from_str_exact("5.4321").unwrap()),
             },
             Transaction {
-                client: 3,
-                tx: 10,
+                client: 3.into(),
+                tx: 10.into(),
                 tx_type: Dispute,
             },
         ]);
 
-        assert_eq!(
-            clients,
-            [
+        assert_client_state_equals(
+            &clients,
+            &[
                 (
-                    3,
+                    3.into(),
                     ClientState {
                         available: AmountType::from_str_exact("0.3456").unwrap(),
                         held: AmountType::from_str_exact("5.4321").unwrap(),
                         locked: false,
-                    }
+                        #[cfg(feature = "detailed-stats")]
+                        deposit_count: 2,
+                        #[cfg(feature = "detailed-stats")]
+                        withdrawal_count: 1,
+                        #[cfg(feature = "detailed-stats")]
+                        dispute_count: 1,
+                        ..Default::default()
+                    },
                 ),
                 (
-                    4,
+                    4.into(),
                     ClientState {
                         available: AmountType::ZERO,
                         held: AmountType::ZERO,
                         locked: false,
-                    }
+                        ..Default::default()
+                    },
                 ),
             ]
             .into_iter()
-            .collect()
+            .collect(),
         );
     }
 
@@ -299,59 +1182,67 @@ mod tests {
     fn test_resolve() {
         let clients = process_transactions_and_return_client_states([
             Transaction {
-                client: 3,
-                tx: 10,
+                client: 3.into(),
+                tx: 10.into(),
                 tx_type: Deposit(AmountType::from_str_exact("5.4321").unwrap()),
             },
             Transaction {
-                client: 3,
-                tx: 10,
+                client: 3.into(),
+                tx: 10.into(),
                 tx_type: Resolve,
             },
             Transaction {
-                client: 3,
-                tx: 10,
+                client: 3.into(),
+                tx: 10.into(),
                 tx_type: Dispute,
             },
             Transaction {
-                client: 4,
-                tx: 10,
+                client: 4.into(),
+                tx: 10.into(),
                 tx_type: Resolve,
             },
             Transaction {
-                client: 3,
-                tx: 200,
+                client: 3.into(),
+                tx: 200.into(),
                 tx_type: Resolve,
             },
             Transaction {
-                client: 3,
-                tx: 10,
+                client: 3.into(),
+                tx: 10.into(),
                 tx_type: Resolve,
             },
         ]);
 
-        assert_eq!(
-            clients,
-            [
+        assert_client_state_equals(
+            &clients,
+            &[
                 (
-                    3,
+                    3.into(),
                     ClientState {
                         available: AmountType::from_str_exact("5.4321").unwrap(),
                         held: AmountType::ZERO,
                         locked: false,
-                    }
+                        #[cfg(feature = "detailed-stats")]
+                        deposit_count: 1,
+                        #[cfg(feature = "detailed-stats")]
+                        dispute_count: 1,
+                        #[cfg(feature = "detailed-stats")]
+                        resolve_count: 1,
+                        ..Default::default()
+                    },
                 ),
                 (
-                    4,
+                    4.into(),
                     ClientState {
                         available: AmountType::ZERO,
                         held: AmountType::ZERO,
                         locked: false,
-                    }
+                        ..Default::default()
+                    },
                 ),
             ]
             .into_iter()
-            .collect()
+            .collect(),
         );
     }
 
@@ -359,69 +1250,834 @@ mod tests {
     fn test_chargeback() {
         let clients = process_transactions_and_return_client_states([
             Transaction {
-                client: 3,
-                tx: 10,
+                client: 3.into(),
+                tx: 10.into(),
                 tx_type: Deposit(AmountType::from_str_exact("5.4321").unwrap()),
             },
             Transaction {
-                client: 3,
-                tx: 10,
+                client: 3.into(),
+                tx: 10.into(),
                 tx_type: Chargeback,
             },
             Transaction {
-                client: 3,
-                tx: 10,
+                client: 3.into(),
+                tx: 10.into(),
                 tx_type: Dispute,
             },
             Transaction {
-                client: 4,
-                tx: 10,
+                client: 4.into(),
+                tx: 10.into(),
                 tx_type: Chargeback,
             },
             Transaction {
-                client: 3,
-                tx: 200,
+                client: 3.into(),
+                tx: 200.into(),
                 tx_type: Chargeback,
             },
             Transaction {
-                client: 3,
-                tx: 10,
+                client: 3.into(),
+                tx: 10.into(),
                 tx_type: Chargeback,
             },
             Transaction {
-                client: 3,
-                tx: 11,
+                client: 3.into(),
+                tx: 11.into(),
                 tx_type: Deposit(AmountType::from_str_exact("5.4321").unwrap()),
             },
             Transaction {
-                client: 3,
-                tx: 12,
+                client: 3.into(),
+                tx: 12.into(),
                 tx_type: Deposit(AmountType::from_str_exact("5.4321").unwrap()),
             },
         ]);
 
-        assert_eq!(
-            clients,
-            [
+        assert_client_state_equals(
+            &clients,
+            &[
                 (
-                    3,
+                    3.into(),
                     ClientState {
-                        available: AmountType::ZERO,
-                        held: AmountType::ZERO,
                         locked: true,
-                    }
+                        pending_locked_transactions: vec![
+                            Transaction {
+                                client: 3.into(),
+                                tx: 11.into(),
+                                tx_type: Deposit(AmountType::from_str_exact("5.4321").unwrap()),
+                            },
+                            Transaction {
+                                client: 3.into(),
+                                tx: 12.into(),
+                                tx_type: Deposit(AmountType::from_str_exact("5.4321").unwrap()),
+                            },
+                        ],
+                        #[cfg(feature = "detailed-stats")]
+                        deposit_count: 1,
+                        #[cfg(feature = "detailed-stats")]
+                        dispute_count: 1,
+                        #[cfg(feature = "detailed-stats")]
+                        chargeback_count: 1,
+                        ..Default::default()
+                    },
                 ),
                 (
-                    4,
+                    4.into(),
                     ClientState {
                         available: AmountType::ZERO,
                         held: AmountType::ZERO,
                         locked: false,
-                    }
+                        ..Default::default()
+                    },
                 ),
             ]
             .into_iter()
-            .collect()
+            .collect(),
         );
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_correction() {
+        let clients = process_transactions_and_return_client_states([
+            Transaction {
+                client: 3.into(),
+                tx: 1.into(),
+                tx_type: Deposit(AmountType::from_str_exact("5").unwrap()),
+            },
+            Transaction {
+                client: 3.into(),
+                tx: 2.into(),
+                tx_type: Correction(AmountType::from_str_exact("-1.5").unwrap(), false),
+            },
+            Transaction {
+                client: 3.into(),
+                tx: 3.into(),
+                tx_type: Correction(AmountType::from_str_exact("-10").unwrap(), false),
+            },
+            Transaction {
+                client: 3.into(),
+                tx: 2.into(),
+                tx_type: Dispute,
+            },
+            Transaction {
+                client: 4.into(),
+                tx: 4.into(),
+                tx_type: Correction(AmountType::from_str_exact("0.25").unwrap(), false),
+            },
+            Transaction {
+                client: 4.into(),
+                tx: 5.into(),
+                tx_type: Correction(AmountType::from_str_exact("-1").unwrap(), true),
+            },
+        ]);
+
+        assert_client_state_equals(
+            &clients,
+            &[
+                (
+                    3.into(),
+                    ClientState {
+                        available: AmountType::from_str_exact("3.5").unwrap(),
+                        held: AmountType::ZERO,
+                        locked: false,
+                        #[cfg(feature = "detailed-stats")]
+                        deposit_count: 1,
+                        ..Default::default()
+                    },
+                ),
+                (
+                    4.into(),
+                    ClientState {
+                        available: AmountType::from_str_exact("-0.75").unwrap(),
+                        held: AmountType::ZERO,
+                        locked: false,
+                        ..Default::default()
+                    },
+                ),
+            ]
+            .into_iter()
+            .collect(),
+        );
+    }
+
+    #[test]
+    fn test_output_record_to_input_record() {
+        let output_record = OutputCsvRecord::from((
+            7.into(),
+            ClientState {
+                available: AmountType::from_str_exact("1.5").unwrap(),
+                held: AmountType::from_str_exact("2").unwrap(),
+                ..Default::default()
+            },
+        ));
+        let transaction =
+            Transaction::try_from(InputCsvRecord::try_from(output_record).unwrap()).unwrap();
+        assert_eq!(
+            transaction,
+            Transaction {
+                client: 7.into(),
+                tx: 7.into(),
+                tx_type: Deposit(AmountType::from_str_exact("3.5").unwrap()),
+            }
+        );
+
+        let locked = OutputCsvRecord::from((
+            7.into(),
+            ClientState {
+                locked: true,
+                ..Default::default()
+            },
+        ));
+        assert!(InputCsvRecord::try_from(locked).is_err());
+
+        for available in ["0", "-1"] {
+            let unfunded = OutputCsvRecord::from((
+                7.into(),
+                ClientState {
+                    available: AmountType::from_str_exact(available).unwrap(),
+                    ..Default::default()
+                },
+            ));
+            assert!(InputCsvRecord::try_from(unfunded).is_err());
+        }
+    }
+
+    #[test]
+    fn test_compact_deposit_log() {
+        let amount = AmountType::from_str_exact("1.5").unwrap();
+        let mut log: DepositLog = [
+            (1.into(), (3.into(), amount, false)),
+            (2.into(), (3.into(), amount, true)),
+            (3.into(), (4.into(), amount, false)),
+        ]
+        .into_iter()
+        .collect();
+
+        compact_deposit_log(&mut log);
+
+        assert_eq!(
+            log,
+            [(2.into(), (3.into(), amount, true))].into_iter().collect()
+        );
+    }
+
+    #[test]
+    fn test_oldest_open_dispute() {
+        let mut log = DepositLog::new();
+        assert_eq!(oldest_open_dispute(&log), None);
+
+        log.insert(1.into(), (1.into(), AmountType::ONE, false));
+        log.insert(5.into(), (1.into(), AmountType::ONE, true));
+        log.insert(3.into(), (2.into(), AmountType::ONE, true));
+        assert_eq!(oldest_open_dispute(&log), Some(3.into()));
+    }
+
+    #[test]
+    fn test_unknown_transaction_policy() {
+        let transactions = || {
+            [
+                Transaction {
+                    client: 3.into(),
+                    tx: 1.into(),
+                    tx_type: Deposit(AmountType::from_str_exact("1").unwrap()),
+                },
+                Transaction {
+                    client: 3.into(),
+                    tx: 7.into(),
+                    tx_type: Dispute,
+                },
+                Transaction {
+                    client: 3.into(),
+                    tx: 2.into(),
+                    tx_type: Deposit(AmountType::from_str_exact("1").unwrap()),
+                },
+            ]
+        };
+        let config_with = |unknown_transaction_policy| ProcessingConfig {
+            unknown_transaction_policy,
+            ..Default::default()
+        };
+
+        let (clients, warnings) = process_transactions_with_config(
+            transactions(),
+            &config_with(UnknownTransactionPolicy::Ignore),
+        )
+        .unwrap();
+        assert_eq!(
+            clients[&ClientId::from(3)].available,
+            AmountType::from_str_exact("2").unwrap()
+        );
+        assert!(warnings.is_empty());
+
+        let (clients, warnings) =
+            process_transactions_with_config(transactions(), &ProcessingConfig::default()).unwrap();
+        assert_eq!(
+            clients[&ClientId::from(3)].available,
+            AmountType::from_str_exact("2").unwrap()
+        );
+        assert_eq!(
+            warnings,
+            vec![ProcessingWarning::UnknownTransaction {
+                client: 3.into(),
+                tx: 7.into(),
+                source_line: None,
+            }]
+        );
+
+        assert!(matches!(
+            process_transactions_with_config(
+                transactions(),
+                &config_with(UnknownTransactionPolicy::Error)
+            ),
+            Err(ProcessingError::UnknownTransaction { client, tx })
+                if client == 3.into() && tx == 7.into()
+        ));
+    }
+
+    #[test]
+    fn test_in_batches() {
+        let transactions: Vec<_> = (1..=5)
+            .map(|tx| Transaction {
+                client: 1.into(),
+                tx: tx.into(),
+                tx_type: Deposit(AmountType::ONE),
+            })
+            .collect();
+
+        let mut progress = Vec::new();
+        let clients = process_transactions_in_batches(transactions, 2, |clients, batch_number| {
+            progress.push((batch_number, clients[&1.into()].available))
+        });
+
+        assert_eq!(
+            progress,
+            vec![
+                (1, AmountType::from(2)),
+                (2, AmountType::from(4)),
+                (3, AmountType::from(5)),
+            ]
+        );
+        assert_eq!(clients[&1.into()].available, AmountType::from(5));
+    }
+
+    #[test]
+    fn test_amount_parsing() {
+        let input_record = |amount: Option<&str>| InputCsvRecord {
+            record_type: DEPOSIT.to_owned(),
+            client: 1.into(),
+            tx: 1.into(),
+            amount: amount.map(str::to_owned),
+            force: None,
+        };
+
+        assert_eq!(
+            Transaction::try_from(input_record(Some(" 10.50 ")))
+                .unwrap()
+                .tx_type,
+            Deposit(AmountType::from_str_exact("10.5").unwrap())
+        );
+        let error = Transaction::try_from(input_record(Some("10,50"))).unwrap_err();
+        assert!(error.to_string().contains("invalid amount \"10,50\""));
+        let error = Transaction::try_from(input_record(Some(""))).unwrap_err();
+        assert!(error.to_string().ends_with("no valid amount found"));
+    }
+
+    #[test]
+    fn test_lenient_conversion() {
+        let input_record = |record_type: &str, amount: Option<&str>| InputCsvRecord {
+            record_type: record_type.to_owned(),
+            client: 1.into(),
+            tx: 1.into(),
+            amount: amount.map(str::to_owned),
+            force: None,
+        };
+
+        assert_eq!(
+            Option::<Transaction>::from(input_record(WITHDRAWAL, Some("2"))),
+            Some(Transaction {
+                client: 1.into(),
+                tx: 1.into(),
+                tx_type: Withdrawal(2.into()),
+            })
+        );
+        assert_eq!(
+            Option::<Transaction>::from(input_record(DEPOSIT, None)),
+            None
+        );
+        assert_eq!(
+            Option::<Transaction>::from(input_record("refund", Some("2"))),
+            None
+        );
+    }
+
+    #[test]
+    fn test_transaction_order() {
+        let transaction = |client: u16, tx: u32, tx_type| Transaction {
+            client: client.into(),
+            tx: tx.into(),
+            tx_type,
+        };
+        let mut transactions = vec![
+            transaction(1, 3, Deposit(1.into())),
+            transaction(2, 1, Deposit(1.into())),
+            transaction(2, 1, Dispute),
+            transaction(1, 2, Withdrawal(1.into())),
+        ];
+
+        assert_eq!(
+            sorted_transactions(transactions.clone()),
+            [
+                transaction(1, 2, Withdrawal(1.into())),
+                transaction(1, 3, Deposit(1.into())),
+                transaction(2, 1, Deposit(1.into())),
+                transaction(2, 1, Dispute),
+            ]
+        );
+        transactions.sort();
+        assert_eq!(
+            transactions,
+            [
+                transaction(2, 1, Deposit(1.into())),
+                transaction(2, 1, Dispute),
+                transaction(1, 2, Withdrawal(1.into())),
+                transaction(1, 3, Deposit(1.into())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_deduplicate_transactions() {
+        let transaction = |tx: u32, tx_type| Transaction {
+            client: 1.into(),
+            tx: tx.into(),
+            tx_type,
+        };
+
+        assert_eq!(
+            deduplicate_transactions(vec![
+                transaction(5, Deposit(1.into())),
+                transaction(5, Dispute),
+                transaction(5, Deposit(2.into())),
+                transaction(6, Deposit(1.into())),
+                transaction(5, Dispute),
+            ]),
+            (
+                vec![
+                    transaction(5, Deposit(1.into())),
+                    transaction(5, Dispute),
+                    transaction(6, Deposit(1.into())),
+                ],
+                2
+            )
+        );
+    }
+
+    #[test]
+    fn test_client_state_hash() {
+        let client_state = |available: &str| ClientState {
+            available: AmountType::from_str_exact(available).unwrap(),
+            ..Default::default()
+        };
+        let states = HashSet::from([client_state("1.0"), client_state("1.00"), client_state("2")]);
+        assert_eq!(states.len(), 2);
+    }
+
+    #[test]
+    fn test_simulate_transaction() {
+        let current_state = ClientState {
+            available: 3.into(),
+            ..Default::default()
+        };
+        let deposit_log = DepositLog::from([(1.into(), (1.into(), 2.into(), false))]);
+        let simulate = |tx: u32, tx_type| {
+            simulate_transaction(
+                &current_state,
+                &deposit_log,
+                &Transaction {
+                    client: 1.into(),
+                    tx: tx.into(),
+                    tx_type,
+                },
+            )
+        };
+
+        let SimulationResult::Applied(new_state) = simulate(1, Dispute) else {
+            panic!("the dispute should apply");
+        };
+        assert_eq!((new_state.available, new_state.held), (1.into(), 2.into()));
+        assert_eq!(
+            simulate(2, Withdrawal(4.into())),
+            SimulationResult::Rejected(IgnoreReason::NotEnoughAvailableFunds)
+        );
+        assert_eq!(
+            simulate(1, Resolve),
+            SimulationResult::Rejected(IgnoreReason::NotUnderDispute)
+        );
+        assert_eq!(current_state.available, 3.into());
+        assert_eq!(deposit_log[&1.into()], (1.into(), 2.into(), false));
+    }
+
+    #[test]
+    fn test_dry_run() {
+        let deposit = Transaction {
+            client: 3.into(),
+            tx: 1.into(),
+            tx_type: Deposit(AmountType::from_str_exact("1").unwrap()),
+        };
+        let withdrawal = Transaction {
+            client: 3.into(),
+            tx: 2.into(),
+            tx_type: Withdrawal(AmountType::from_str_exact("2").unwrap()),
+        };
+        let dispute = Transaction {
+            client: 4.into(),
+            tx: 1.into(),
+            tx_type: Dispute,
+        };
+        let resolve = Transaction {
+            client: 3.into(),
+            tx: 1.into(),
+            tx_type: Resolve,
+        };
+
+        assert_eq!(
+            process_transactions_dry_run([
+                deposit,
+                withdrawal.clone(),
+                dispute.clone(),
+                resolve.clone()
+            ]),
+            vec![
+                (withdrawal, IgnoreReason::NotEnoughAvailableFunds),
+                (dispute, IgnoreReason::DisputeClientMismatch),
+                (resolve, IgnoreReason::NotUnderDispute),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_filtered() {
+        let transactions = vec![
+            Transaction {
+                client: 1.into(),
+                tx: 1.into(),
+                tx_type: Deposit(AmountType::from_str_exact("5").unwrap()),
+            },
+            Transaction {
+                client: 2.into(),
+                tx: 2.into(),
+                tx_type: Deposit(AmountType::from_str_exact("5").unwrap()),
+            },
+            Transaction {
+                client: 1.into(),
+                tx: 3.into(),
+                tx_type: Withdrawal(AmountType::from_str_exact("2").unwrap()),
+            },
+        ];
+
+        let clients = process_transactions_filtered(
+            transactions,
+            &Not(Box::new(ByTransactionType(Withdrawal(AmountType::ZERO)))),
+        );
+        assert_eq!(clients.len(), 2);
+        assert_eq!(
+            clients[&1.into()].available,
+            AmountType::from_str_exact("5").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_top_n() {
+        let deposit = |client: u16, tx: u32, amount: &str| Transaction {
+            client: client.into(),
+            tx: tx.into(),
+            tx_type: Deposit(AmountType::from_str_exact(amount).unwrap()),
+        };
+        let transactions = vec![
+            deposit(1, 1, "1"),
+            deposit(2, 2, "5"),
+            deposit(3, 3, "3"),
+            deposit(4, 4, "4"),
+            Transaction {
+                client: 2.into(),
+                tx: 2.into(),
+                tx_type: Dispute,
+            },
+        ];
+
+        let top_n = process_transactions_and_return_top_n(transactions.clone(), 2);
+        assert_eq!(
+            top_n
+                .iter()
+                .map(|(client_id, _)| *client_id)
+                .collect::<Vec<_>>(),
+            vec![2.into(), 4.into()]
+        );
+        assert_eq!(top_n[0].1.held, AmountType::from_str_exact("5").unwrap());
+
+        assert_eq!(
+            process_transactions_and_return_top_n(transactions.clone(), 10).len(),
+            4
+        );
+        assert!(process_transactions_and_return_top_n(transactions, 0).is_empty());
+    }
+
+    #[test]
+    fn test_sub() {
+        let before = ClientState {
+            available: AmountType::from_str_exact("3").unwrap(),
+            held: AmountType::from_str_exact("1").unwrap(),
+            ..Default::default()
+        };
+        let after = ClientState {
+            available: AmountType::from_str_exact("1.5").unwrap(),
+            held: AmountType::from_str_exact("2.5").unwrap(),
+            locked: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            after - before,
+            ClientStateDelta {
+                available_delta: AmountType::from_str_exact("-1.5").unwrap().into(),
+                held_delta: AmountType::from_str_exact("1.5").unwrap().into(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_withdraw_all() {
+        let mut client_state = ClientState {
+            available: AmountType::from_str_exact("3").unwrap(),
+            held: AmountType::from_str_exact("1").unwrap(),
+            ..Default::default()
+        };
+
+        assert!(matches!(
+            client_state.withdraw_all(false),
+            Err(ProcessingError::FundsHeld(_))
+        ));
+        assert_eq!(
+            client_state.withdraw_all(true).unwrap(),
+            AmountType::from_str_exact("3").unwrap()
+        );
+        assert_eq!(client_state.available, AmountType::ZERO);
+        assert_eq!(client_state.held, AmountType::from_str_exact("1").unwrap());
+    }
+
+    #[cfg(feature = "admin-operations")]
+    #[test]
+    fn test_reset_held() {
+        let mut client_state = ClientState {
+            available: AmountType::from_str_exact("3").unwrap(),
+            held: AmountType::from_str_exact("1.5").unwrap(),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            client_state.reset_held(),
+            AmountType::from_str_exact("1.5").unwrap()
+        );
+        assert_eq!(
+            client_state.available,
+            AmountType::from_str_exact("4.5").unwrap()
+        );
+        assert_eq!(client_state.held, AmountType::ZERO);
+    }
+
+    #[test]
+    fn test_report() {
+        let deposit = Transaction {
+            client: 3.into(),
+            tx: 1.into(),
+            tx_type: Deposit(AmountType::from_str_exact("2").unwrap()),
+        };
+        let dispute = Transaction {
+            client: 3.into(),
+            tx: 1.into(),
+            tx_type: Dispute,
+        };
+        let resolve = Transaction {
+            client: 3.into(),
+            tx: 9.into(),
+            tx_type: Resolve,
+        };
+
+        let report =
+            process_transactions_into_report([deposit.clone(), dispute.clone(), resolve.clone()]);
+
+        assert_eq!(report.stats.transactions_read, 3);
+        assert_eq!(report.stats.clients, 1);
+        assert_eq!(
+            report.warnings,
+            vec![ProcessingWarning::UnknownTransaction {
+                client: 3.into(),
+                tx: 9.into(),
+                source_line: None,
+            }]
+        );
+        assert_eq!(
+            report.audit_entries,
+            vec![
+                AuditEntry {
+                    transaction: deposit,
+                    ignore_reason: None,
+                    available_delta: AmountType::from_str_exact("2").unwrap().into(),
+                    held_delta: AmountType::ZERO.into(),
+                },
+                AuditEntry {
+                    transaction: dispute,
+                    ignore_reason: None,
+                    available_delta: AmountType::from_str_exact("-2").unwrap().into(),
+                    held_delta: AmountType::from_str_exact("2").unwrap().into(),
+                },
+                AuditEntry {
+                    transaction: resolve,
+                    ignore_reason: Some(IgnoreReason::NoDisputeFound),
+                    available_delta: AmountType::ZERO.into(),
+                    held_delta: AmountType::ZERO.into(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_duplicate_deposit() {
+        let transactions = || {
+            [1, 1, 2].map(|tx| Transaction {
+                client: 3.into(),
+                tx: tx.into(),
+                tx_type: Deposit(AmountType::from_str_exact("1").unwrap()),
+            })
+        };
+
+        let (clients, _) =
+            process_transactions_with_config(transactions(), &ProcessingConfig::default()).unwrap();
+        assert_eq!(
+            clients[&ClientId::from(3)].available,
+            AmountType::from_str_exact("3").unwrap()
+        );
+
+        assert!(matches!(
+            process_transactions_with_config(
+                transactions(),
+                &ProcessingConfig {
+                    strict_mode: true,
+                    ..Default::default()
+                }
+            ),
+            Err(ProcessingError::DuplicateTransactionId(tx)) if tx == 1.into()
+        ));
+    }
+
+    #[test]
+    fn test_strict_amount_validator() {
+        let transactions = [("10.1", 1), ("10.1000", 2)].map(|(amount, tx)| Transaction {
+            client: 1.into(),
+            tx: tx.into(),
+            tx_type: Deposit(AmountType::from_str_exact(amount).unwrap()),
+        });
+
+        let (clients, _) = process_transactions_with_config(
+            transactions,
+            &ProcessingConfig {
+                strict_amounts: Some(StrictAmountValidator::default()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            clients[&ClientId::from(1)].available,
+            AmountType::from_str_exact("10.1").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_replay_transactions_up_to() {
+        let transactions = [1, 2, 3].map(|tx| Transaction {
+            client: 1.into(),
+            tx: tx.into(),
+            tx_type: Deposit(tx.into()),
+        });
+
+        let clients = replay_transactions_up_to(&transactions, ReplayLimit::ByCount(2));
+        assert_eq!(clients[&ClientId::from(1)].available, 3.into());
+        let clients = replay_transactions_up_to(&transactions, ReplayLimit::ByCount(10));
+        assert_eq!(clients[&ClientId::from(1)].available, 6.into());
+    }
+
+    #[test]
+    fn test_client_state_with_methods() {
+        let client_state = ClientState::default()
+            .with_available(3.into())
+            .with_held(2.into())
+            .with_locked(true);
+
+        assert_eq!(
+            (
+                client_state.available,
+                client_state.held,
+                client_state.locked
+            ),
+            (3.into(), 2.into(), true)
+        );
+    }
+
+    #[test]
+    fn test_error_count() {
+        let transactions = [
+            Transaction {
+                client: 1.into(),
+                tx: 1.into(),
+                tx_type: Deposit(1.into()),
+            },
+            Transaction {
+                client: 1.into(),
+                tx: 2.into(),
+                tx_type: Withdrawal(2.into()),
+            },
+            Transaction {
+                client: 1.into(),
+                tx: 3.into(),
+                tx_type: Dispute,
+            },
+        ];
+
+        let (clients, ignored) =
+            process_transactions_and_return_client_states_with_error_count(transactions);
+        assert_eq!(clients[&ClientId::from(1)].available, 1.into());
+        assert_eq!(ignored, 2);
+    }
+
+    #[test]
+    fn test_can_withdraw_and_can_dispute() {
+        let client_state = ClientState::default().with_available(2.into());
+        assert!(client_state.can_withdraw(2.into()));
+        assert!(!client_state.can_withdraw(3.into()));
+        assert!(client_state.can_dispute(2.into()));
+        assert!(!client_state.can_dispute(3.into()));
+
+        let client_state = client_state.with_locked(true);
+        assert!(!client_state.can_withdraw(1.into()));
+        assert!(!client_state.can_dispute(1.into()));
+    }
+
+    #[test]
+    fn test_transactions_touching_client() {
+        let transaction = |client: u16, tx: u32, tx_type| Transaction {
+            client: client.into(),
+            tx: tx.into(),
+            tx_type,
+        };
+        let transactions = [
+            transaction(1, 1, Deposit(1.into())),
+            transaction(2, 2, Deposit(1.into())),
+            transaction(2, 1, Dispute),
+            transaction(1, 3, Withdrawal(1.into())),
+            transaction(3, 2, Dispute),
+        ];
+
+        assert_eq!(
+            transactions_touching_client(&transactions, 1.into()),
+            [&transactions[0], &transactions[2], &transactions[3]]
+        );
+    }
+}
@@ -0,0 +1,167 @@
+use super::{ClientId, Transaction, TransactionId, TransactionType};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Suspicious, but the engine handles it, e.g. a feed that is out of order.
+    Warning,
+    /// The engine will ignore the transaction, or apply it with an effect the feed can hardly have meant: a deposit
+    /// that reuses a transaction id replaces the earlier deposit on record, which can then no longer be disputed.
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationIssue {
+    pub severity: Severity,
+    pub tx_id: TransactionId,
+    pub message: String,
+}
+
+/// Checks the ordering of a transaction stream as it arrives, before or alongside processing, to catch feeds that are
+/// out of order or reference transactions they never sent. Deposits, withdrawals and corrections introduce
+/// transaction ids; disputes, resolves and chargebacks reference earlier ones.
+#[derive(Debug, Default)]
+pub struct TransactionSequenceValidator {
+    max_disputes_per_client: usize,
+    last_tx: Option<TransactionId>,
+    seen: HashSet<TransactionId>,
+    open_disputes: HashMap<ClientId, HashSet<TransactionId>>,
+}
+
+impl TransactionSequenceValidator {
+    pub fn new(max_disputes_per_client: usize) -> Self {
+        Self {
+            max_disputes_per_client,
+            ..Default::default()
+        }
+    }
+
+    /// Checks `tx` against the transactions validated so far, and records it.
+    pub fn validate_next(&mut self, tx: &Transaction) -> Vec<ValidationIssue> {
+        let mut issues = Vec::new();
+        let mut issue = |severity, message| {
+            issues.push(ValidationIssue {
+                severity,
+                tx_id: tx.tx,
+                message,
+            })
+        };
+
+        match tx.tx_type {
+            TransactionType::Deposit(_)
+            | TransactionType::Withdrawal(_)
+            | TransactionType::Correction(..) => {
+                if let Some(last_tx) = self.last_tx.filter(|&last_tx| tx.tx < last_tx) {
+                    issue(
+                        Severity::Warning,
+                        format!("transaction id decreased from {last_tx}"),
+                    );
+                }
+                if !self.seen.insert(tx.tx) {
+                    issue(
+                        Severity::Error,
+                        "transaction id was already used".to_owned(),
+                    );
+                }
+                self.last_tx = self.last_tx.max(Some(tx.tx));
+            }
+            TransactionType::Dispute | TransactionType::Resolve | TransactionType::Chargeback
+                if !self.seen.contains(&tx.tx) =>
+            {
+                issue(
+                    Severity::Error,
+                    "references a transaction that hasn't been seen yet".to_owned(),
+                );
+            }
+            TransactionType::Dispute => {
+                let open_disputes = self.open_disputes.entry(tx.client).or_default();
+                if open_disputes.insert(tx.tx) && self.max_disputes_per_client < open_disputes.len()
+                {
+                    issue(
+                        Severity::Warning,
+                        format!(
+                            "{:#} has {} open disputes, more than {}",
+                            tx.client,
+                            open_disputes.len(),
+                            self.max_disputes_per_client
+                        ),
+                    );
+                }
+            }
+            TransactionType::Resolve | TransactionType::Chargeback => {
+                if let Some(open_disputes) = self.open_disputes.get_mut(&tx.client) {
+                    open_disputes.remove(&tx.tx);
+                }
+            }
+        }
+        issues
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transaction_sequence_validator() {
+        let transaction = |tx: u32, tx_type| Transaction {
+            client: 1.into(),
+            tx: tx.into(),
+            tx_type,
+        };
+        let mut validator = TransactionSequenceValidator::new(1);
+        let mut validate = |tx, tx_type| {
+            validator
+                .validate_next(&transaction(tx, tx_type))
+                .into_iter()
+                .map(|issue| (issue.severity, issue.message))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(validate(2, TransactionType::Deposit(1.into())), []);
+        assert_eq!(
+            validate(1, TransactionType::Deposit(1.into())),
+            [(
+                Severity::Warning,
+                "transaction id decreased from 2".to_owned()
+            )]
+        );
+        assert_eq!(
+            validate(3, TransactionType::Dispute),
+            [(
+                Severity::Error,
+                "references a transaction that hasn't been seen yet".to_owned()
+            )]
+        );
+        assert_eq!(validate(1, TransactionType::Dispute), []);
+        assert_eq!(
+            validate(2, TransactionType::Dispute),
+            [(
+                Severity::Warning,
+                "client 1 has 2 open disputes, more than 1".to_owned()
+            )]
+        );
+        assert_eq!(validate(2, TransactionType::Resolve), []);
+        assert_eq!(validate(1, TransactionType::Dispute), []);
+
+        // Corrections introduce transaction ids too.
+        assert_eq!(
+            validate(5, TransactionType::Correction((-1).into(), false)),
+            []
+        );
+        assert_eq!(
+            validate(5, TransactionType::Deposit(1.into())),
+            [(
+                Severity::Error,
+                "transaction id was already used".to_owned()
+            )]
+        );
+        assert_eq!(
+            validate(4, TransactionType::Correction(1.into(), true)),
+            [(
+                Severity::Warning,
+                "transaction id decreased from 5".to_owned()
+            )]
+        );
+    }
+}